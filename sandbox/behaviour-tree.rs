@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use bevy_2d_template::behaviour_tree::{BehaviourTree, BehaviourTreePlugin, Node, Status};
+
+struct Player;
+
+struct Enemy {
+    speed: f32,
+}
+
+fn main() {
+    App::build()
+        .add_default_plugins()
+        .add_plugin(BehaviourTreePlugin)
+        .add_resource(ClearColor(Color::BLACK))
+        .add_startup_system(setup.system())
+        .add_system(move_player_system.system())
+        .run();
+}
+
+fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands
+        .spawn(Camera2dComponents::default())
+        .spawn(SpriteComponents {
+            material: materials.add(Color::WHITE.into()),
+            sprite: Sprite::new(Vec2::new(10.0, 10.0)),
+            ..Default::default()
+        })
+        .with(Player);
+    for (x, y) in [(-200.0, 100.0), (200.0, -150.0)].iter() {
+        commands
+            .spawn(SpriteComponents {
+                material: materials.add(Color::RED.into()),
+                transform: Transform {
+                    translation: Vec3::new(*x, *y, 0.0),
+                    ..Default::default()
+                },
+                sprite: Sprite::new(Vec2::new(10.0, 10.0)),
+                ..Default::default()
+            })
+            .with(Enemy { speed: 60.0 })
+            .with(BehaviourTree::new(Node::reactive_selector(vec![
+                // chase the player while it's close by
+                Node::reactive_sequence(vec![
+                    Node::condition(|entity, world, _| {
+                        distance_to_player(entity, world).map_or(false, |d| d < 150.0)
+                    }),
+                    Node::action(chase_player),
+                ]),
+                // otherwise stay put
+                Node::action(|_, _, _| Status::Running),
+            ])));
+    }
+}
+
+fn player_position(world: &World) -> Option<Vec3> {
+    for (transform, _) in &mut world.query::<(&Transform, &Player)>() {
+        return Some(transform.translation);
+    }
+    None
+}
+
+fn distance_to_player(entity: Entity, world: &World) -> Option<f32> {
+    let position = world.get::<Transform>(entity).ok()?.translation;
+    Some((player_position(world)? - position).length())
+}
+
+fn chase_player(entity: Entity, world: &mut World, resources: &mut Resources) -> Status {
+    let target = match player_position(world) {
+        Some(target) => target,
+        None => return Status::Failure,
+    };
+    let delta_seconds = resources.get::<Time>().unwrap().delta_seconds;
+    let speed = world.get::<Enemy>(entity).unwrap().speed;
+    let mut transform = world.get_mut::<Transform>(entity).unwrap();
+    let offset = target - transform.translation;
+    if offset.length() < 1.0 {
+        Status::Success
+    } else {
+        transform.translation += offset.normalize() * (speed * delta_seconds).min(offset.length());
+        Status::Running
+    }
+}
+
+fn move_player_system(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut player_query: Query<(&Player, &mut Transform)>,
+) {
+    let mut direction = Vec3::zero();
+    if keyboard_input.pressed(KeyCode::Left) {
+        direction -= Vec3::unit_x();
+    }
+    if keyboard_input.pressed(KeyCode::Right) {
+        direction += Vec3::unit_x();
+    }
+    if keyboard_input.pressed(KeyCode::Down) {
+        direction -= Vec3::unit_y();
+    }
+    if keyboard_input.pressed(KeyCode::Up) {
+        direction += Vec3::unit_y();
+    }
+    for (_, mut transform) in &mut player_query.iter() {
+        transform.translation += direction * 100.0 * time.delta_seconds;
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use bevy_2d_template::display_diagnostic::BenchmarkPlugin;
+
+struct Position(Vec2);
+
+struct Velocity(Vec2);
+
+/// Runs headless; pass `--update-baseline` to save the results as the new baseline
+fn main() {
+    let result = BenchmarkPlugin {
+        update_baseline: std::env::args().any(|arg| arg == "--update-baseline"),
+        ..BenchmarkPlugin::new("sandbox/benchmark-baseline.ron")
+    }
+    .run(|app| {
+        app.add_startup_system(setup.system())
+            .add_system(move_system.system())
+            .add_system(bounce_system.system());
+    });
+    match result {
+        Ok(report) => println!("{}", report),
+        Err(report) => {
+            println!("{}", report);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn setup(mut commands: Commands) {
+    for i in 0..10_000 {
+        let angle = i as f32 * 0.1;
+        commands.spawn((
+            Position(Vec2::new(0.0, 0.0)),
+            Velocity(Vec2::new(angle.cos(), angle.sin()) * 100.0),
+        ));
+    }
+}
+
+fn move_system(time: Res<Time>, mut query: Query<(&mut Position, &Velocity)>) {
+    for (mut position, velocity) in &mut query.iter() {
+        position.0 += velocity.0 * time.delta_seconds;
+    }
+}
+
+fn bounce_system(mut query: Query<(&Position, &mut Velocity)>) {
+    for (position, mut velocity) in &mut query.iter() {
+        if position.0.x().abs() > 400.0 {
+            velocity.0.set_x(-velocity.0.x());
+        }
+        if position.0.y().abs() > 300.0 {
+            velocity.0.set_y(-velocity.0.y());
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use bevy_2d_template::state_machine::{
+    AssetStateEvent, AssetStateMachine, AssetStateMachinePlugin, StateMachineCallbacks,
+};
+
+struct Enemy;
+
+fn main() {
+    App::build()
+        .add_default_plugins()
+        .add_plugin(AssetStateMachinePlugin { hot_reload: true })
+        .add_startup_system(setup.system())
+        .add_system(send_events_system.system())
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut callbacks: ResMut<StateMachineCallbacks>,
+) {
+    callbacks
+        .add_action("alert", |entity, _, _| {
+            println!("{:?} saw the player", entity)
+        })
+        .add_action("despawn", |entity, world, _| {
+            world.despawn(entity).ok();
+        });
+    // edit assets/fsm/enemy.fsm while this runs to change the enemy's behaviour
+    commands
+        .spawn((Enemy,))
+        .with(AssetStateMachine::new(asset_server.load("fsm/enemy.fsm")));
+}
+
+fn send_events_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut events: ResMut<Events<AssetStateEvent>>,
+    mut enemy_query: Query<(Entity, &Enemy, &AssetStateMachine)>,
+) {
+    let event = if keyboard_input.just_released(KeyCode::S) {
+        "PlayerSeen"
+    } else if keyboard_input.just_released(KeyCode::L) {
+        "PlayerLost"
+    } else if keyboard_input.just_released(KeyCode::H) {
+        "Hit"
+    } else {
+        return;
+    };
+    for (entity, _, machine) in &mut enemy_query.iter() {
+        println!("{:?} is {:?}, sending {}", entity, machine.current(), event);
+        events.send(AssetStateEvent {
+            entity,
+            event: event.to_string(),
+        });
+    }
+}
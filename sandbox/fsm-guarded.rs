@@ -0,0 +1,82 @@
+use bevy_2d_template::state_machine::{TransitionError, TransitionTable};
+
+#[derive(Debug, Clone, PartialEq)]
+enum State {
+    Waiting { waiting_time: usize },
+    Filling { rate: usize },
+    Done,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    NothingHappened,
+    InsertBottle { size: usize },
+    BottleFull,
+    BottleEjected,
+}
+
+fn transitions() -> TransitionTable<State, Event> {
+    TransitionTable::new()
+        .on(
+            |s| matches!(s, State::Waiting { .. }),
+            |e| matches!(e, Event::NothingHappened),
+            |s, _| match s {
+                State::Waiting { waiting_time } => State::Waiting {
+                    waiting_time: waiting_time + 1,
+                },
+                _ => unreachable!(),
+            },
+        )
+        // the size of the bottle decides how quickly it's filled, but empty or big bottles don't fit
+        .on_if(
+            |s| matches!(s, State::Waiting { .. }),
+            |e| matches!(e, Event::InsertBottle { .. }),
+            "bottle fits",
+            |_, e| matches!(e, Event::InsertBottle { size } if (1..=2).contains(size)),
+            |_, e| match e {
+                Event::InsertBottle { size } => State::Filling { rate: 10 / size },
+                _ => unreachable!(),
+            },
+        )
+        .on(
+            |s| matches!(s, State::Filling { .. }),
+            |e| matches!(e, Event::BottleFull),
+            |_, _| State::Done,
+        )
+        .on(
+            |s| matches!(s, State::Done),
+            |e| matches!(e, Event::BottleEjected),
+            |_, _| State::Waiting { waiting_time: 0 },
+        )
+}
+
+fn main() {
+    let transitions = transitions();
+    let mut state = State::Waiting { waiting_time: 0 };
+
+    let events = [
+        Event::NothingHappened,
+        Event::InsertBottle { size: 3 },
+        Event::InsertBottle { size: 2 },
+        Event::BottleFull,
+        Event::BottleEjected,
+        Event::NothingHappened,
+        Event::BottleFull,
+    ];
+
+    for event in events.iter() {
+        match transitions.next(&state, event) {
+            Ok(next) => {
+                println!("__ Transition from {:?} to {:?}", state, next);
+                state = next;
+            }
+            Err(TransitionError::GuardsFailed { guards, .. }) => {
+                println!("__ {:?} ignored, failed {:?}", event, guards);
+            }
+            Err(error) => {
+                println!("{:?}", error);
+                break;
+            }
+        }
+    }
+}
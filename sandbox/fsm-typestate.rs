@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use bevy_2d_template::state_machine::{StateChanged, StateMachine, StateMachinePlugin, Transition};
+use bevy_2d_template::typestate_machine;
+
+typestate_machine! {
+    enum Light in lights { Green, Yellow, Red }
+    enum LightEvent in light_events { GreenToYellow, YellowToGreen, YellowToRed, RedToGreen }
+    transitions {
+        Green + GreenToYellow => Yellow,
+        Yellow + YellowToGreen => Green,
+        Yellow + YellowToRed => Red,
+        Red + RedToGreen => Green,
+    }
+}
+
+fn main() {
+    // the typestate form still rejects illegal transitions at compile time
+    let state = lights::Green;
+    let state: lights::Yellow = state.next(light_events::GreenToYellow);
+    let state: lights::Red = state.next(light_events::YellowToRed);
+    dbg!(&state);
+
+    // the declaration can be exported as a diagram
+    Light::graph().assert_all_reachable();
+    println!("{}", Light::graph().to_mermaid());
+
+    // and the enumerable form drives a Bevy resource
+    App::build()
+        .add_default_plugins()
+        .add_plugin(StateMachinePlugin::new(Light::default(), Light::next))
+        .add_resource(LightTimer(Timer::from_seconds(1.0, true)))
+        .add_system(cycle_lights_system.system())
+        .add_system(print_lights_system.system())
+        .run();
+}
+
+struct LightTimer(Timer);
+
+fn cycle_lights_system(
+    time: Res<Time>,
+    mut timer: ResMut<LightTimer>,
+    light: Res<StateMachine<Light>>,
+    mut events: ResMut<Events<LightEvent>>,
+) {
+    timer.0.tick(time.delta_seconds);
+    if timer.0.finished {
+        events.send(match light.current() {
+            Light::Green(_) => light_events::GreenToYellow.into(),
+            Light::Yellow(_) => light_events::YellowToRed.into(),
+            Light::Red(_) => light_events::RedToGreen.into(),
+        });
+    }
+}
+
+fn print_lights_system(
+    mut reader: Local<EventReader<StateChanged<Light>>>,
+    events: Res<Events<StateChanged<Light>>>,
+) {
+    for changed in reader.iter(&events) {
+        println!("Transition from {:?} to {:?}", changed.from, changed.to);
+    }
+}
@@ -0,0 +1,529 @@
+// https://www.gamasutra.com/blogs/ChrisSimpson/20140717/221339/Behavior_trees_for_AI_How_they_work.php
+// https://web.archive.org/web/20150214085327/http://guineashots.com/2014/07/25/an-introduction-to-behavior-trees-part-1/
+
+use bevy::prelude::*;
+
+/// The result of ticking a behaviour tree [Node]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// A leaf that does some work for an entity, with full access to the ECS
+pub type Action = Box<dyn FnMut(Entity, &mut World, &mut Resources) -> Status + Send + Sync>;
+
+/// A leaf that checks something about an entity, succeeding if it holds and failing otherwise
+pub type Condition = Box<dyn Fn(Entity, &World, &Resources) -> bool + Send + Sync>;
+
+/// A node of a [BehaviourTree]
+pub enum Node {
+    /// Ticks its children in order, until one fails or is still running, resuming at the running
+    /// child on the next tick
+    Sequence {
+        children: Vec<Node>,
+        current: usize,
+    },
+    /// Ticks its children in order, until one succeeds or is still running, resuming at the
+    /// running child on the next tick
+    Selector {
+        children: Vec<Node>,
+        current: usize,
+    },
+    /// Like a [Sequence](Node::Sequence), but starts from the first child every tick, so the
+    /// children before a running one are checked again; a running child that isn't reached is
+    /// [reset](Node::reset)
+    ReactiveSequence {
+        children: Vec<Node>,
+        running: Option<usize>,
+    },
+    /// Like a [Selector](Node::Selector), but starts from the first child every tick, so the
+    /// children before a running one are tried again; a running child that isn't reached is
+    /// [reset](Node::reset)
+    ReactiveSelector {
+        children: Vec<Node>,
+        running: Option<usize>,
+    },
+    /// Ticks all its children every tick, succeeding once `successes` of them have succeeded,
+    /// and failing once that is no longer possible
+    Parallel {
+        children: Vec<Node>,
+        statuses: Vec<Status>,
+        successes: usize,
+    },
+    Decorator(Decorator, Box<Node>),
+    Condition(Condition),
+    Action(Action),
+}
+
+/// Changes the result of the node it wraps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decorator {
+    /// Swaps success and failure
+    Invert,
+    /// Turns failure into success
+    Succeed,
+    /// Runs the wrapped node to completion `times` times, failing if it fails
+    Repeat { times: usize, done: usize },
+    /// Runs the wrapped node until it fails, then succeeds
+    UntilFailure,
+}
+
+/// A behaviour tree component, ticked once per frame by the [BehaviourTreePlugin]
+pub struct BehaviourTree {
+    root: Option<Node>,
+    status: Status,
+}
+
+/// An App Plugin that ticks every [BehaviourTree]
+#[derive(Default)]
+pub struct BehaviourTreePlugin;
+
+impl Plugin for BehaviourTreePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(Self::tick_behaviour_trees_system.thread_local_system());
+    }
+}
+
+impl BehaviourTreePlugin {
+    pub fn tick_behaviour_trees_system(world: &mut World, resources: &mut Resources) {
+        let mut entities = Vec::new();
+        for (entity, _) in &mut world.query::<(Entity, &BehaviourTree)>() {
+            entities.push(entity);
+        }
+        for entity in entities {
+            // the tree is taken out of the world while it runs, so its leaves can borrow the world
+            let root = match world.get_mut::<BehaviourTree>(entity) {
+                Ok(mut tree) => tree.root.take(),
+                Err(_) => None,
+            };
+            if let Some(mut root) = root {
+                let status = root.tick(entity, world, resources);
+                if let Ok(mut tree) = world.get_mut::<BehaviourTree>(entity) {
+                    tree.root = Some(root);
+                    tree.status = status;
+                }
+            }
+        }
+    }
+}
+
+impl BehaviourTree {
+    pub fn new(root: Node) -> Self {
+        BehaviourTree {
+            root: Some(root),
+            status: Status::Running,
+        }
+    }
+
+    /// The status returned by the most recent tick
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+impl Node {
+    pub fn sequence(children: Vec<Node>) -> Self {
+        Node::Sequence {
+            children,
+            current: 0,
+        }
+    }
+
+    pub fn selector(children: Vec<Node>) -> Self {
+        Node::Selector {
+            children,
+            current: 0,
+        }
+    }
+
+    pub fn reactive_sequence(children: Vec<Node>) -> Self {
+        Node::ReactiveSequence {
+            children,
+            running: None,
+        }
+    }
+
+    pub fn reactive_selector(children: Vec<Node>) -> Self {
+        Node::ReactiveSelector {
+            children,
+            running: None,
+        }
+    }
+
+    pub fn parallel(successes: usize, children: Vec<Node>) -> Self {
+        Node::Parallel {
+            statuses: vec![Status::Running; children.len()],
+            children,
+            successes,
+        }
+    }
+
+    pub fn invert(child: Node) -> Self {
+        Node::Decorator(Decorator::Invert, Box::new(child))
+    }
+
+    pub fn succeed(child: Node) -> Self {
+        Node::Decorator(Decorator::Succeed, Box::new(child))
+    }
+
+    pub fn repeat(times: usize, child: Node) -> Self {
+        Node::Decorator(Decorator::Repeat { times, done: 0 }, Box::new(child))
+    }
+
+    pub fn until_failure(child: Node) -> Self {
+        Node::Decorator(Decorator::UntilFailure, Box::new(child))
+    }
+
+    pub fn condition(
+        condition: impl Fn(Entity, &World, &Resources) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Node::Condition(Box::new(condition))
+    }
+
+    pub fn action(
+        action: impl FnMut(Entity, &mut World, &mut Resources) -> Status + Send + Sync + 'static,
+    ) -> Self {
+        Node::Action(Box::new(action))
+    }
+
+    pub fn tick(&mut self, entity: Entity, world: &mut World, resources: &mut Resources) -> Status {
+        match self {
+            Node::Sequence { children, current } => {
+                Self::tick_composite(children, current, Status::Success, entity, world, resources)
+            }
+            Node::Selector { children, current } => {
+                Self::tick_composite(children, current, Status::Failure, entity, world, resources)
+            }
+            Node::ReactiveSequence { children, running } => {
+                Self::tick_reactive(children, running, Status::Success, entity, world, resources)
+            }
+            Node::ReactiveSelector { children, running } => {
+                Self::tick_reactive(children, running, Status::Failure, entity, world, resources)
+            }
+            Node::Parallel {
+                children,
+                statuses,
+                successes,
+            } => {
+                for (child, status) in children.iter_mut().zip(statuses.iter_mut()) {
+                    if *status == Status::Running {
+                        *status = child.tick(entity, world, resources);
+                    }
+                }
+                let succeeded = statuses.iter().filter(|s| **s == Status::Success).count();
+                let failed = statuses.iter().filter(|s| **s == Status::Failure).count();
+                let result = if succeeded >= *successes {
+                    Status::Success
+                } else if statuses.len() - failed < *successes {
+                    Status::Failure
+                } else {
+                    Status::Running
+                };
+                if result != Status::Running {
+                    statuses.iter_mut().for_each(|s| *s = Status::Running);
+                }
+                result
+            }
+            Node::Decorator(decorator, child) => match decorator {
+                Decorator::Invert => match child.tick(entity, world, resources) {
+                    Status::Success => Status::Failure,
+                    Status::Failure => Status::Success,
+                    Status::Running => Status::Running,
+                },
+                Decorator::Succeed => match child.tick(entity, world, resources) {
+                    Status::Running => Status::Running,
+                    _ => Status::Success,
+                },
+                Decorator::Repeat { times, done } => match child.tick(entity, world, resources) {
+                    Status::Running => Status::Running,
+                    Status::Failure => {
+                        *done = 0;
+                        Status::Failure
+                    }
+                    Status::Success => {
+                        *done += 1;
+                        if *done >= *times {
+                            *done = 0;
+                            Status::Success
+                        } else {
+                            Status::Running
+                        }
+                    }
+                },
+                Decorator::UntilFailure => match child.tick(entity, world, resources) {
+                    Status::Failure => Status::Success,
+                    _ => Status::Running,
+                },
+            },
+            Node::Condition(condition) => {
+                if condition(entity, world, resources) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Action(action) => action(entity, world, resources),
+        }
+    }
+
+    /// Forgets the progress of this node and the nodes under it, as if it had never been ticked;
+    /// actions keep any state of their own
+    pub fn reset(&mut self) {
+        match self {
+            Node::Sequence { children, current } | Node::Selector { children, current } => {
+                *current = 0;
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::ReactiveSequence { children, running }
+            | Node::ReactiveSelector { children, running } => {
+                *running = None;
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::Parallel {
+                children, statuses, ..
+            } => {
+                statuses.iter_mut().for_each(|s| *s = Status::Running);
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::Decorator(decorator, child) => {
+                if let Decorator::Repeat { done, .. } = decorator {
+                    *done = 0;
+                }
+                child.reset();
+            }
+            Node::Condition(_) | Node::Action(_) => {}
+        }
+    }
+
+    /// Ticks `children` from `current` onwards, moving on while they return `continue_on`
+    fn tick_composite(
+        children: &mut [Node],
+        current: &mut usize,
+        continue_on: Status,
+        entity: Entity,
+        world: &mut World,
+        resources: &mut Resources,
+    ) -> Status {
+        while let Some(child) = children.get_mut(*current) {
+            let status = child.tick(entity, world, resources);
+            if status != continue_on {
+                if status != Status::Running {
+                    *current = 0;
+                }
+                return status;
+            }
+            *current += 1;
+        }
+        *current = 0;
+        continue_on
+    }
+
+    /// Ticks `children` from the first, moving on while they return `continue_on`, and resets the
+    /// child left `running` by the last tick if it's no longer reached
+    fn tick_reactive(
+        children: &mut [Node],
+        running: &mut Option<usize>,
+        continue_on: Status,
+        entity: Entity,
+        world: &mut World,
+        resources: &mut Resources,
+    ) -> Status {
+        let mut result = (children.len(), continue_on);
+        for (index, child) in children.iter_mut().enumerate() {
+            let status = child.tick(entity, world, resources);
+            if status != continue_on {
+                result = (index, status);
+                break;
+            }
+        }
+        let (stopped, status) = result;
+        if let Some(previous) = *running {
+            if previous > stopped {
+                children[previous].reset();
+            }
+        }
+        *running = if status == Status::Running {
+            Some(stopped)
+        } else {
+            None
+        };
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// An action returning `statuses` in turn, then the last of them, counting its ticks
+    fn scripted(statuses: Vec<Status>, ticks: &Arc<AtomicUsize>) -> Node {
+        let statuses = Mutex::new(statuses);
+        let ticks = ticks.clone();
+        Node::action(move |_, _, _| {
+            ticks.fetch_add(1, Ordering::SeqCst);
+            let mut statuses = statuses.lock().unwrap();
+            if statuses.len() > 1 {
+                statuses.remove(0)
+            } else {
+                statuses[0]
+            }
+        })
+    }
+
+    /// A condition holding while `flag` is set
+    fn flag_condition(flag: &Arc<AtomicBool>) -> Node {
+        let flag = flag.clone();
+        Node::condition(move |_, _, _| flag.load(Ordering::SeqCst))
+    }
+
+    fn counter() -> Arc<AtomicUsize> {
+        Arc::new(AtomicUsize::new(0))
+    }
+
+    fn ticks(counter: &Arc<AtomicUsize>) -> usize {
+        counter.load(Ordering::SeqCst)
+    }
+
+    /// Ticks `node` once for each of `expected`, checking the statuses it returns
+    fn assert_ticks(node: &mut Node, expected: &[Status]) {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let entity = world.spawn((0u32,));
+        let statuses = expected
+            .iter()
+            .map(|_| node.tick(entity, &mut world, &mut resources))
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, expected);
+    }
+
+    #[test]
+    fn sequence_resumes_at_running_child() {
+        let (first, second, third) = (counter(), counter(), counter());
+        let mut node = Node::sequence(vec![
+            scripted(vec![Status::Success], &first),
+            scripted(vec![Status::Running, Status::Success], &second),
+            scripted(vec![Status::Success], &third),
+        ]);
+        assert_ticks(&mut node, &[Status::Running, Status::Success]);
+        assert_eq!((ticks(&first), ticks(&second), ticks(&third)), (1, 2, 1));
+        // starts again from the first child once finished
+        assert_ticks(&mut node, &[Status::Success]);
+        assert_eq!(ticks(&first), 2);
+    }
+
+    #[test]
+    fn selector_resumes_at_running_child() {
+        let (first, second) = (counter(), counter());
+        let mut node = Node::selector(vec![
+            scripted(vec![Status::Failure], &first),
+            scripted(vec![Status::Running, Status::Failure], &second),
+        ]);
+        assert_ticks(&mut node, &[Status::Running, Status::Failure]);
+        assert_eq!((ticks(&first), ticks(&second)), (1, 2));
+    }
+
+    #[test]
+    fn parallel_fails_when_successes_are_impossible() {
+        let children = counter();
+        let mut node = Node::parallel(
+            3,
+            vec![
+                scripted(vec![Status::Success], &children),
+                scripted(vec![Status::Success], &children),
+            ],
+        );
+        assert_ticks(&mut node, &[Status::Failure]);
+    }
+
+    #[test]
+    fn parallel_resets_once_finished() {
+        let (running, failing) = (counter(), counter());
+        let mut node = Node::parallel(
+            1,
+            vec![
+                scripted(vec![Status::Running, Status::Success], &running),
+                scripted(vec![Status::Failure], &failing),
+            ],
+        );
+        assert_ticks(&mut node, &[Status::Running, Status::Success]);
+        // the failed child isn't ticked again until the node has finished
+        assert_eq!(ticks(&failing), 1);
+        assert_ticks(&mut node, &[Status::Success]);
+        assert_eq!(ticks(&failing), 2);
+    }
+
+    #[test]
+    fn repeat_runs_child_times_over() {
+        let child = counter();
+        let mut node = Node::repeat(3, scripted(vec![Status::Success], &child));
+        assert_ticks(
+            &mut node,
+            &[
+                Status::Running,
+                Status::Running,
+                Status::Success,
+                Status::Running,
+            ],
+        );
+        assert_eq!(ticks(&child), 4);
+    }
+
+    #[test]
+    fn until_failure_succeeds_on_failure() {
+        let child = counter();
+        let mut node = Node::until_failure(scripted(
+            vec![Status::Success, Status::Running, Status::Failure],
+            &child,
+        ));
+        assert_ticks(
+            &mut node,
+            &[Status::Running, Status::Running, Status::Success],
+        );
+    }
+
+    #[test]
+    fn reactive_selector_rechecks_condition_once_it_holds() {
+        let close = Arc::new(AtomicBool::new(false));
+        let (chase, idle) = (counter(), counter());
+        let mut node = Node::reactive_selector(vec![
+            Node::reactive_sequence(vec![
+                flag_condition(&close),
+                scripted(vec![Status::Running], &chase),
+            ]),
+            scripted(vec![Status::Running], &idle),
+        ]);
+        assert_ticks(&mut node, &[Status::Running, Status::Running]);
+        assert_eq!((ticks(&chase), ticks(&idle)), (0, 2));
+        close.store(true, Ordering::SeqCst);
+        assert_ticks(&mut node, &[Status::Running, Status::Running]);
+        assert_eq!((ticks(&chase), ticks(&idle)), (2, 2));
+        close.store(false, Ordering::SeqCst);
+        assert_ticks(&mut node, &[Status::Running]);
+        assert_eq!((ticks(&chase), ticks(&idle)), (2, 3));
+    }
+
+    #[test]
+    fn reactive_sequence_resets_abandoned_child() {
+        let ready = Arc::new(AtomicBool::new(true));
+        let (first, second) = (counter(), counter());
+        let mut node = Node::reactive_sequence(vec![
+            flag_condition(&ready),
+            Node::sequence(vec![
+                scripted(vec![Status::Success], &first),
+                scripted(vec![Status::Running], &second),
+            ]),
+        ]);
+        assert_ticks(&mut node, &[Status::Running, Status::Running]);
+        assert_eq!((ticks(&first), ticks(&second)), (1, 2));
+        ready.store(false, Ordering::SeqCst);
+        assert_ticks(&mut node, &[Status::Failure]);
+        // the abandoned sequence starts again from its first child
+        ready.store(true, Ordering::SeqCst);
+        assert_ticks(&mut node, &[Status::Running]);
+        assert_eq!((ticks(&first), ticks(&second)), (2, 3));
+    }
+}
@@ -1,7 +1,29 @@
 use super::font::fonts;
 
-use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+mod benchmark;
+mod format;
+mod game_diagnostics;
+mod graph;
+mod record;
+mod threshold;
+pub use benchmark::{
+    BenchmarkBaseline, BenchmarkPlugin, BenchmarkReport, BenchmarkRow, BenchmarkStats,
+};
+pub use format::{DiagnosticFormat, DiagnosticFormats};
+pub use game_diagnostics::{
+    ComponentCountDiagnosticsPlugin, EntityCountDiagnosticsPlugin, FontDiagnosticsPlugin,
+    StageTimeDiagnosticsPlugin,
+};
+pub use graph::{DiagnosticHistory, DiagnosticsGraphStyle};
+pub use record::{DiagnosticsRecorder, RecordFormat, RecordSettings};
+pub use threshold::{DiagnosticThresholds, Threshold, ThresholdEvent, ThresholdLevel};
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, FrameTimeDiagnosticsPlugin};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 // use bevy::app::prelude::*;
 // use bevy::core::{Time, Timer};
@@ -12,15 +34,113 @@ pub struct DisplayDiagnosticsPlugin {
     pub debug: bool,
     pub wait_duration: Duration,
     pub filter: Option<Vec<DiagnosticId>>,
+    pub style: DisplayDiagnosticsStyle,
+    /// The mode the overlay starts in, unless a persisted mode is found
+    pub mode: DisplayMode,
+    /// Cycles through the [DisplayMode]s when pressed
+    pub toggle_key: Option<KeyCode>,
+    /// Where to save the current [DisplayMode], so it survives restarts
+    pub persist_path: Option<PathBuf>,
+    pub record: RecordSettings,
+    pub thresholds: Vec<Threshold>,
+    /// How each diagnostic's values are shown, with defaults for the frame time diagnostics
+    pub formats: Vec<(DiagnosticId, DiagnosticFormat)>,
+}
+
+/// How much of the diagnostics overlay is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisplayMode {
+    Hidden,
+    /// Frames per second only
+    Compact,
+    Full,
+}
+
+/// Where the diagnostics overlay sits on screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// How each diagnostic is laid out in the overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticsLayout {
+    /// One line per diagnostic: `name: value (avg average)`
+    Lines,
+    /// A table with separate name, value and average columns
+    Table,
+}
+
+/// The look of the overlay spawned by the [DisplayDiagnosticsPlugin]
+#[derive(Debug, Clone)]
+pub struct DisplayDiagnosticsStyle {
+    pub anchor: Anchor,
+    /// Space between the overlay and the edges of the window, and around the text
+    pub margin: f32,
+    pub font: &'static str,
+    pub font_size: f32,
+    pub color: Color,
+    /// Colour of diagnostics past their warning [Threshold]
+    pub warning_color: Color,
+    /// Colour of diagnostics past their critical [Threshold]
+    pub critical_color: Color,
+    /// Colour of a panel drawn behind the text, if any
+    pub background: Option<Color>,
+    pub layout: DiagnosticsLayout,
+    /// Most diagnostics shown; each has its own text entities, spawned up front
+    pub max_lines: usize,
+    /// Draws a rolling graph of each diagnostic under the text
+    pub graph: Option<DiagnosticsGraphStyle>,
 }
 
 /// State used by the [DisplayDiagnosticsPlugin]
 pub struct DisplayDiagnosticsState {
     timer: Timer,
     filter: Option<Vec<DiagnosticId>>,
+    mode: DisplayMode,
+    saved_mode: DisplayMode,
+    persist_path: Option<PathBuf>,
+    /// Redraws the overlay without waiting for the timer
+    dirty: bool,
+    /// Filtered diagnostics already warned about not being registered
+    missing: HashSet<DiagnosticId>,
+    /// The text of each line's columns, reused from one update to the next
+    lines: Vec<(Vec<String>, ThresholdLevel)>,
+    /// How many of `lines` are in use
+    line_count: usize,
+    /// The diagnostics shown by the last update, reused from one update to the next
+    displayed_ids: Vec<DiagnosticId>,
 }
 
-pub struct DisplayText(bool, String);
+/// What a line's column shows, only copied to its [Text] when it changes
+pub struct DisplayText {
+    visible: bool,
+    value: String,
+    level: ThresholdLevel,
+    changed: bool,
+}
+
+/// The line and column of the overlay a [DisplayText] shows
+pub struct DisplayCell {
+    line: usize,
+    column: usize,
+}
+
+/// A line of the overlay, hidden while it has no diagnostic to show
+pub struct DisplayLine(usize);
+
+/// The panel behind the overlay's text
+pub struct DisplayPanel;
+
+struct DisplayToggleKey(Option<KeyCode>);
 
 impl Default for DisplayDiagnosticsPlugin {
     fn default() -> Self {
@@ -28,19 +148,236 @@ impl Default for DisplayDiagnosticsPlugin {
             debug: false,
             wait_duration: Duration::from_secs(1),
             filter: None,
+            style: DisplayDiagnosticsStyle::default(),
+            mode: DisplayMode::Full,
+            toggle_key: Some(KeyCode::F3),
+            persist_path: None,
+            record: RecordSettings::default(),
+            thresholds: Vec::new(),
+            formats: DiagnosticFormat::frame_time_defaults(),
+        }
+    }
+}
+
+impl DisplayDiagnosticsState {
+    fn new(plugin: &DisplayDiagnosticsPlugin, mode: DisplayMode) -> Self {
+        DisplayDiagnosticsState {
+            timer: Timer::new(plugin.wait_duration, true),
+            filter: plugin.filter.clone(),
+            mode,
+            saved_mode: mode,
+            persist_path: plugin.persist_path.clone(),
+            dirty: true,
+            missing: HashSet::new(),
+            lines: Vec::new(),
+            line_count: 0,
+            displayed_ids: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> DisplayMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: DisplayMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.dirty = true;
+        }
+    }
+
+    pub fn show(&mut self) {
+        self.set_mode(DisplayMode::Full);
+    }
+
+    pub fn hide(&mut self) {
+        self.set_mode(DisplayMode::Hidden);
+    }
+
+    pub fn filter(&self) -> Option<&[DiagnosticId]> {
+        self.filter.as_deref()
+    }
+
+    /// Changes which diagnostics are shown, `None` showing them all
+    pub fn set_filter(&mut self, filter: Option<Vec<DiagnosticId>>) {
+        self.filter = filter;
+        self.dirty = true;
+    }
+
+    /// Warns, once per id, that a filtered diagnostic isn't registered, returning whether it did
+    fn warn_missing(&mut self, id: DiagnosticId) -> bool {
+        let warn = self.missing.insert(id);
+        if warn {
+            eprintln!(
+                "Diagnostic {} is not registered, displaying it as n/a",
+                id.0
+            );
+        }
+        warn
+    }
+
+    /// The ids of the diagnostics shown in the current mode
+    fn displayed<'a>(
+        &'a self,
+        diagnostics: &'a Diagnostics,
+    ) -> impl Iterator<Item = DiagnosticId> + 'a {
+        let (compact, full) = match self.mode {
+            DisplayMode::Hidden => (None, None),
+            DisplayMode::Compact => (Some(FrameTimeDiagnosticsPlugin::FPS), None),
+            DisplayMode::Full => (None, Some(filtered_ids(self.filter(), diagnostics))),
+        };
+        compact.into_iter().chain(full.into_iter().flatten())
+    }
+}
+
+/// The ids a filter selects, in its order, or every registered diagnostic's without one; shared
+/// by the overlay, the recorder and the benchmark so they always agree
+fn filtered_ids<'a>(
+    filter: Option<&'a [DiagnosticId]>,
+    diagnostics: &'a Diagnostics,
+) -> impl Iterator<Item = DiagnosticId> + 'a {
+    let all = match filter {
+        Some(_) => None,
+        None => Some(diagnostics.iter().map(|d| d.id)),
+    };
+    filter
+        .into_iter()
+        .flatten()
+        .copied()
+        .chain(all.into_iter().flatten())
+}
+
+/// Whether a filter selects the diagnostic `id`
+fn is_filtered(filter: Option<&[DiagnosticId]>, id: DiagnosticId) -> bool {
+    filter.map_or(true, |filter| filter.contains(&id))
+}
+
+impl DisplayMode {
+    pub fn next(self) -> Self {
+        match self {
+            DisplayMode::Hidden => DisplayMode::Compact,
+            DisplayMode::Compact => DisplayMode::Full,
+            DisplayMode::Full => DisplayMode::Hidden,
+        }
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let ron = std::fs::read_to_string(path).ok()?;
+        ron::de::from_str(&ron).ok()
+    }
+
+    fn save(self, path: &Path) {
+        let result = ron::ser::to_string(&self)
+            .map_err(|error| error.to_string())
+            .and_then(|ron| std::fs::write(path, ron).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            eprintln!("Could not save diagnostics display mode: {}", error);
+        }
+    }
+}
+
+impl Default for DisplayDiagnosticsStyle {
+    fn default() -> Self {
+        DisplayDiagnosticsStyle {
+            anchor: Anchor::TopRight,
+            margin: 5.0,
+            font: fonts::JETBRAINSMONO_LIGHT,
+            font_size: 10.0,
+            color: Color::WHITE,
+            warning_color: Color::rgb(1.0, 1.0, 0.0),
+            critical_color: Color::RED,
+            background: None,
+            layout: DiagnosticsLayout::Lines,
+            max_lines: 16,
+            graph: None,
+        }
+    }
+}
+
+impl Anchor {
+    /// How to justify and align the overlay inside a node covering the window
+    ///
+    /// The UI's y axis points up, here and throughout the overlay: the end of the cross axis is
+    /// the top of the window, `ColumnReverse` stacks children down from the top, `FlexStart`
+    /// aligns them to the bottom, and `position.top` is measured from the bottom.
+    fn alignment(self) -> (JustifyContent, AlignItems) {
+        let justify_content = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => JustifyContent::FlexStart,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => JustifyContent::Center,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => JustifyContent::FlexEnd,
+        };
+        let align_items = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => AlignItems::FlexEnd,
+            Anchor::Left | Anchor::Center | Anchor::Right => AlignItems::Center,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => AlignItems::FlexStart,
+        };
+        (justify_content, align_items)
+    }
+}
+
+impl DiagnosticsLayout {
+    fn columns(self) -> usize {
+        match self {
+            DiagnosticsLayout::Lines => 1,
+            DiagnosticsLayout::Table => 3,
+        }
+    }
+
+    /// How wide a column is, so the columns line up from one line's texts to the next
+    fn column_width(self, column: usize, font_size: f32) -> Val {
+        // roughly the advance of a monospace font
+        let char_width = font_size * 0.6;
+        match (self, column) {
+            (DiagnosticsLayout::Table, 0) => Val::Px(16.0 * char_width),
+            (DiagnosticsLayout::Table, _) => Val::Px(12.0 * char_width),
+            (DiagnosticsLayout::Lines, _) => Val::Auto,
         }
     }
 }
 
 impl Plugin for DisplayDiagnosticsPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_resource(DisplayDiagnosticsState {
-            timer: Timer::new(self.wait_duration, true),
-            filter: self.filter.clone(),
-        });
+        let mode = self
+            .persist_path
+            .as_deref()
+            .and_then(DisplayMode::load)
+            .unwrap_or(self.mode);
+        app.add_resource(DisplayDiagnosticsState::new(self, mode));
+        app.add_resource(self.style.clone());
+        app.add_resource(DisplayToggleKey(self.toggle_key));
+        let formats = DiagnosticFormats::new(self.formats.clone());
+        app.add_resource(DiagnosticHistory::new(
+            self.style
+                .graph
+                .as_ref()
+                .map_or(0, |graph| graph.samples)
+                .max(formats.max_smoothing()),
+        ));
+        app.add_resource(formats);
+        app.init_resource::<graph::GraphBuffers>();
         // app.init_resource::<FontMap>();
         app.add_startup_system(Self::setup.system());
         app.add_system(Self::display_diagnostics_text_system.system());
+        app.add_system(Self::toggle_display_system.system());
+        app.add_system(Self::persist_display_mode_system.system());
+        app.add_system_to_stage(
+            stage::POST_UPDATE,
+            graph::diagnostic_history_system.system(),
+        );
+        app.add_system_to_stage(stage::POST_UPDATE, graph::graph_values_system.system());
+        app.add_system_to_stage(stage::POST_UPDATE, graph::diagnostics_graph_system.system());
+        app.add_resource(DiagnosticsRecorder::new(self.record.clone()));
+        app.add_startup_system(record::start_recording_system.system());
+        app.add_system_to_stage(
+            stage::POST_UPDATE,
+            record::record_diagnostics_system.system(),
+        );
+        app.add_resource(DiagnosticThresholds::new(self.thresholds.clone()));
+        app.add_event::<ThresholdEvent>();
+        app.add_system_to_stage(
+            stage::POST_UPDATE,
+            threshold::check_thresholds_system.system(),
+        );
         if self.debug {
             app.add_system_to_stage(
                 stage::POST_UPDATE,
@@ -63,82 +400,331 @@ impl DisplayDiagnosticsPlugin {
         }
     }
 
-    fn display_diagnostic(buffer: &mut String, diagnostic: &Diagnostic) {
-        if let Some(value) = diagnostic.value() {
-            *buffer += &*format!("{:<12}: {:<10.6}", diagnostic.name, value);
+    pub fn with_style(mut self, style: DisplayDiagnosticsStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn persist_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.persist_path = Some(path.into());
+        self
+    }
+
+    pub fn recording(mut self, record: RecordSettings) -> Self {
+        self.record = record;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: Threshold) -> Self {
+        self.thresholds.push(threshold);
+        self
+    }
+
+    /// Shows `id` with `format`, replacing its default format if it has one
+    pub fn with_format(mut self, id: DiagnosticId, format: DiagnosticFormat) -> Self {
+        self.formats.retain(|(format_id, _)| *format_id != id);
+        self.formats.push((id, format));
+        self
+    }
+
+    /// Writes a diagnostic's columns into `line`, returning false if it has no value yet
+    fn display_diagnostic(
+        line: &mut [String],
+        diagnostic: &Diagnostic,
+        format: &DiagnosticFormat,
+        history: &DiagnosticHistory,
+    ) -> bool {
+        let value = match diagnostic.value() {
+            Some(value) if format.smoothing > 1 => history
+                .mean(diagnostic.id, format.smoothing)
+                .unwrap_or(value),
+            Some(value) => value,
+            None => return false,
+        };
+        if let [buffer] = line {
+            write!(buffer, "{:<12}: ", diagnostic.name).unwrap();
+            let start = buffer.len();
+            format.write(buffer, value);
+            let width = buffer.len() - start;
+            buffer.extend(std::iter::repeat(' ').take(10usize.saturating_sub(width)));
             if let Some(average) = diagnostic.average() {
-                *buffer += &*format!(" (avg {:.6})", average);
+                buffer.push_str(" (avg ");
+                format.write(buffer, average);
+                buffer.push(')');
             }
-            *buffer += "\n";
+        } else if let [names, values, averages] = line {
+            names.push_str(&diagnostic.name);
+            format.write(values, value);
+            if let Some(average) = diagnostic.average() {
+                averages.push_str("avg ");
+                format.write(averages, average);
+            }
+        }
+        true
+    }
+
+    /// Shows a diagnostic that isn't registered (yet) as `n/a`
+    fn display_missing(line: &mut [String], id: DiagnosticId) {
+        if let [buffer] = line {
+            write!(buffer, "{:<12}: n/a", id.0).unwrap();
+        } else if let [names, values, _] = line {
+            write!(names, "{}", id.0).unwrap();
+            values.push_str("n/a");
         }
     }
 
     pub fn setup(
         mut commands: Commands,
         asset_server: Res<AssetServer>,
+        mut materials: ResMut<Assets<ColorMaterial>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        style: Res<DisplayDiagnosticsStyle>,
         // mut font_map: ResMut<FontMap>,
     ) {
+        let font = asset_server.load(style.font);
+        let transparent = materials.add(Color::rgba(0.0, 0.0, 0.0, 0.0).into());
+        let background = match style.background {
+            Some(color) => materials.add(color.into()),
+            None => transparent.clone(),
+        };
+        let (justify_content, align_items) = style.anchor.alignment();
         commands
-            .spawn(TextComponents {
+            // covers the window, to place the panel at the anchor
+            .spawn(NodeComponents {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    padding: Rect::all(Val::Px(style.margin)),
+                    justify_content,
+                    align_items,
+                    ..Default::default()
+                },
+                material: transparent.clone(),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeComponents {
+                        style: Style {
+                            flex_direction: FlexDirection::ColumnReverse,
+                            padding: Rect::all(Val::Px(style.margin)),
+                            ..Default::default()
+                        },
+                        material: background,
+                        ..Default::default()
+                    })
+                    .with(DisplayPanel)
+                    .with_children(|panel| {
+                        for line in 0..style.max_lines {
+                            panel
+                                .spawn(NodeComponents {
+                                    style: Style {
+                                        display: if line == 0 {
+                                            Display::Flex
+                                        } else {
+                                            Display::None
+                                        },
+                                        flex_direction: FlexDirection::Row,
+                                        ..Default::default()
+                                    },
+                                    material: transparent.clone(),
+                                    ..Default::default()
+                                })
+                                .with(DisplayLine(line))
+                                .with_children(|row| {
+                                    Self::spawn_columns(row, font.clone(), line, &style)
+                                });
+                        }
+                        if let Some(ref graph) = style.graph {
+                            graph::spawn_graphs(
+                                panel,
+                                &mut materials,
+                                &mut meshes,
+                                transparent.clone(),
+                                graph,
+                            );
+                        }
+                    });
+            });
+    }
+
+    fn spawn_columns(
+        row: &mut ChildBuilder,
+        font: Handle<Font>,
+        line: usize,
+        style: &DisplayDiagnosticsStyle,
+    ) {
+        for column in 0..style.layout.columns() {
+            let value = if line == 0 && column == 0 {
+                "Waiting..."
+            } else {
+                ""
+            };
+            row.spawn(TextComponents {
                 text: Text {
-                    font: asset_server.load(fonts::JETBRAINSMONO_LIGHT),
-                    value: "Waiting...".to_string(),
+                    font: font.clone(),
+                    value: value.to_string(),
                     style: TextStyle {
-                        color: Color::WHITE,
-                        font_size: 10.0,
+                        color: style.color,
+                        font_size: style.font_size,
                     },
                 },
                 style: Style {
-                    position_type: PositionType::Absolute,
-                    position: Rect {
-                        top: Val::Px(5.0),
-                        right: Val::Px(5.0),
+                    min_size: Size::new(
+                        style.layout.column_width(column, style.font_size),
+                        Val::Auto,
+                    ),
+                    margin: Rect {
+                        right: Val::Px(style.font_size),
                         ..Default::default()
                     },
                     ..Default::default()
                 },
                 ..Default::default()
             })
-            .with(DisplayText(true, "".to_string()));
+            .with(DisplayText {
+                visible: true,
+                value: value.to_string(),
+                level: ThresholdLevel::Normal,
+                changed: false,
+            })
+            .with(DisplayCell { line, column });
+        }
     }
 
+    // the read-only resources are each also used on their own by other systems, so they're
+    // taken one by one rather than grouped for this system alone
+    #[allow(clippy::too_many_arguments)]
     pub fn store_diagnostics_text_system(
         mut state: ResMut<DisplayDiagnosticsState>,
         time: Res<Time>,
         diagnostics: Res<Diagnostics>,
-        mut text_query: Query<&mut DisplayText>,
+        style: Res<DisplayDiagnosticsStyle>,
+        thresholds: Res<DiagnosticThresholds>,
+        formats: Res<DiagnosticFormats>,
+        history: Res<DiagnosticHistory>,
+        mut text_query: Query<(&mut DisplayText, &DisplayCell)>,
+        mut line_query: Query<(&DisplayLine, &mut Style)>,
     ) {
         state.timer.tick(time.delta_seconds);
-        if state.timer.finished {
-            // if let Some((&mut text, display_text)) = text_query.iter().iter().next() {
-            for mut display_text in &mut text_query.iter() {
-                if display_text.0 {
-                    let mut buffer = String::new();
-                    if let Some(ref filter) = state.filter {
-                        for diagnostic in filter.iter().map(|id| diagnostics.get(*id).unwrap()) {
-                            Self::display_diagnostic(&mut buffer, diagnostic);
-                        }
-                    } else {
-                        for diagnostic in diagnostics.iter() {
-                            Self::display_diagnostic(&mut buffer, diagnostic);
-                        }
-                    }
-                    display_text.1 = buffer;
-                } else {
-                    display_text.1 = "".to_string();
+        if !(state.timer.finished || state.dirty) {
+            return;
+        }
+        state.dirty = false;
+        let columns = style.layout.columns();
+        let state = &mut *state;
+        state.line_count = 0;
+        let mut displayed = std::mem::take(&mut state.displayed_ids);
+        displayed.clear();
+        displayed.extend(state.displayed(&diagnostics));
+        for &id in displayed.iter() {
+            if state.line_count == style.max_lines {
+                break;
+            }
+            if state.lines.len() == state.line_count {
+                state
+                    .lines
+                    .push((vec![String::new(); columns], ThresholdLevel::Normal));
+            }
+            let diagnostic = diagnostics.get(id);
+            if diagnostic.is_none() {
+                state.warn_missing(id);
+            }
+            let (line, level) = &mut state.lines[state.line_count];
+            line.iter_mut().for_each(String::clear);
+            *level = thresholds.level(id);
+            let shown = match diagnostic {
+                Some(diagnostic) => {
+                    Self::display_diagnostic(line, diagnostic, formats.get(id), &history)
                 }
+                None => {
+                    Self::display_missing(line, id);
+                    true
+                }
+            };
+            if shown {
+                state.line_count += 1;
+            }
+        }
+        state.displayed_ids = displayed;
+        let visible = state.mode != DisplayMode::Hidden;
+        for (mut display_text, cell) in &mut text_query.iter() {
+            let (value, level) = match state.lines[..state.line_count].get(cell.line) {
+                Some((line, level)) => (line[cell.column].as_str(), *level),
+                None => ("", ThresholdLevel::Normal),
+            };
+            let value = if visible { value } else { "" };
+            if display_text.value != value || display_text.level != level {
+                display_text.value.clear();
+                display_text.value.push_str(value);
+                display_text.level = level;
+                display_text.changed = true;
+            }
+            if display_text.visible != visible {
+                display_text.visible = visible;
+            }
+        }
+        for (line, mut line_style) in &mut line_query.iter() {
+            let display = if line.0 < state.line_count.max(1) {
+                Display::Flex
+            } else {
+                Display::None
+            };
+            if line_style.display != display {
+                line_style.display = display;
             }
         }
     }
 
-    fn display_diagnostics_text_system(mut text_query: Query<(&mut Text, &DisplayText)>) {
-        for (mut text, display_text) in &mut text_query.iter() {
-            if text.value != display_text.1 {
-                text.value = display_text.1.clone();
+    fn display_diagnostics_text_system(
+        style: Res<DisplayDiagnosticsStyle>,
+        mut text_query: Query<(&mut Text, &mut DisplayText)>,
+        mut panel_query: Query<(&mut Draw, &DisplayPanel)>,
+    ) {
+        let mut visible = false;
+        for (mut text, mut display_text) in &mut text_query.iter() {
+            visible |= display_text.visible;
+            // only changed lines are touched, so the others aren't laid out again
+            if display_text.changed {
+                display_text.changed = false;
+                text.value.clear();
+                text.value.push_str(&display_text.value);
+                text.style.color = match display_text.level {
+                    ThresholdLevel::Normal => style.color,
+                    ThresholdLevel::Warning => style.warning_color,
+                    ThresholdLevel::Critical => style.critical_color,
+                };
+            }
+        }
+        for (mut draw, _) in &mut panel_query.iter() {
+            if draw.is_visible != visible {
+                draw.is_visible = visible;
             }
         }
     }
 
+    fn toggle_display_system(
+        mut state: ResMut<DisplayDiagnosticsState>,
+        toggle_key: Res<DisplayToggleKey>,
+        keyboard_input: Res<Input<KeyCode>>,
+    ) {
+        if let Some(key) = toggle_key.0 {
+            if keyboard_input.just_pressed(key) {
+                let mode = state.mode.next();
+                state.set_mode(mode);
+            }
+        }
+    }
+
+    fn persist_display_mode_system(mut state: ResMut<DisplayDiagnosticsState>) {
+        if state.mode != state.saved_mode {
+            if let Some(ref path) = state.persist_path {
+                state.mode.save(path);
+            }
+            state.saved_mode = state.mode;
+        }
+    }
+
     pub fn display_diagnostics_debug_system(
         mut state: ResMut<DisplayDiagnosticsState>,
         time: Res<Time>,
@@ -148,15 +734,54 @@ impl DisplayDiagnosticsPlugin {
         if state.timer.finished {
             println!("Diagnostics (Debug):");
             println!("{}", "-".repeat(93));
-            if let Some(ref filter) = state.filter {
-                for diagnostic in filter.iter().map(|id| diagnostics.get(*id).unwrap()) {
-                    println!("{:#?}\n", diagnostic);
-                }
-            } else {
-                for diagnostic in diagnostics.iter() {
-                    println!("{:#?}\n", diagnostic);
+            let ids = filtered_ids(state.filter(), &diagnostics).collect::<Vec<_>>();
+            for id in ids {
+                match diagnostics.get(id) {
+                    Some(diagnostic) => println!("{:#?}\n", diagnostic),
+                    None => {
+                        state.warn_missing(id);
+                        println!("{}: n/a\n", id.0);
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::System;
+
+    #[test]
+    fn unregistered_diagnostic_shows_na_and_warns_once() {
+        let missing = DiagnosticId::from_u128(0x2b0f8e6c9a7d4e3f8c1b5a6d7e8f9a0b);
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let plugin = DisplayDiagnosticsPlugin::default();
+        resources.insert(DisplayDiagnosticsState::new(&plugin, DisplayMode::Full));
+        resources.insert(Time::default());
+        resources.insert(Diagnostics::default());
+        resources.insert(plugin.style.clone());
+        resources.insert(DiagnosticThresholds::new(Vec::new()));
+        resources.insert(DiagnosticFormats::new(Vec::new()));
+        resources.insert(DiagnosticHistory::new(1));
+        let mut system = DisplayDiagnosticsPlugin::store_diagnostics_text_system.system();
+        system.initialize(&mut resources);
+        for _ in 0..2 {
+            // changing the filter redraws without waiting for the timer
+            resources
+                .get_mut::<DisplayDiagnosticsState>()
+                .unwrap()
+                .set_filter(Some(vec![missing]));
+            system.update_archetype_access(&world);
+            system.run(&mut world, &mut resources);
+            system.run_thread_local(&mut world, &mut resources);
+        }
+        let mut state = resources.get_mut::<DisplayDiagnosticsState>().unwrap();
+        assert_eq!(state.line_count, 1);
+        assert!(state.lines[0].0.concat().ends_with("n/a"));
+        // already warned about while redrawing
+        assert!(!state.warn_missing(missing));
+    }
+}
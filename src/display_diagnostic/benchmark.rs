@@ -0,0 +1,455 @@
+use super::{filtered_ids, is_filtered, DiagnosticHistory};
+
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::core::CorePlugin;
+use bevy::diagnostic::{DiagnosticId, Diagnostics, DiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::type_registry::TypeRegistryPlugin;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Runs an app without a window for a number of frames, and compares its diagnostics against a
+/// stored baseline, failing with a report if any of them regressed
+///
+/// ```ignore
+/// let report = BenchmarkPlugin::new("sandbox/benchmark-baseline.ron").run(|app| {
+///     app.add_startup_system(spawn_entities.system())
+///         .add_system(move_entities_system.system());
+/// });
+/// if report.is_err() {
+///     std::process::exit(1);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BenchmarkPlugin {
+    /// Frames measured, after the warm-up
+    pub frames: usize,
+    /// Frames run before measuring, so startup doesn't count
+    pub warmup: usize,
+    /// The diagnostics measured, like [DisplayDiagnosticsPlugin::filtered](super::DisplayDiagnosticsPlugin::filtered),
+    /// `None` measuring them all
+    pub filter: Option<Vec<DiagnosticId>>,
+    pub baseline: PathBuf,
+    /// Saves the results as the new baseline instead of failing on regressions; without it a
+    /// missing baseline fails the benchmark
+    pub update_baseline: bool,
+    /// How much worse than the baseline a diagnostic may get, as a fraction of the baseline
+    pub tolerance: f64,
+    /// Tolerances for specific diagnostics, overriding `tolerance`
+    pub tolerances: Vec<(DiagnosticId, f64)>,
+    /// Diagnostics that regress by going down rather than up
+    pub higher_is_better: Vec<DiagnosticId>,
+}
+
+/// What a benchmark measured for one diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkStats {
+    pub mean: f64,
+    /// The 95th percentile on the regressing side: the 5th if higher is better
+    pub tail: f64,
+}
+
+/// The stats of each measured diagnostic, by name
+pub type BenchmarkBaseline = BTreeMap<String, BenchmarkStats>;
+
+/// How each measured diagnostic compares to its baseline
+#[derive(Debug, Clone)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub baseline: PathBuf,
+    pub rows: Vec<BenchmarkRow>,
+    /// Why the benchmark failed whatever its rows show, e.g. a missing baseline
+    pub error: Option<String>,
+    /// Whether the results were saved as the new baseline, so regressions don't fail it
+    pub updated_baseline: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkRow {
+    pub diagnostic: String,
+    pub baseline: Option<BenchmarkStats>,
+    pub current: Option<BenchmarkStats>,
+    pub tolerance: f64,
+    pub higher_is_better: bool,
+}
+
+struct BenchmarkState {
+    frame: usize,
+    history: DiagnosticHistory,
+    /// Whether the report has been written
+    done: bool,
+}
+
+/// Where the benchmark system leaves its report for [BenchmarkPlugin::run]
+#[derive(Default)]
+struct BenchmarkResult(Arc<Mutex<Option<BenchmarkReport>>>);
+
+impl Default for BenchmarkPlugin {
+    fn default() -> Self {
+        BenchmarkPlugin {
+            frames: 600,
+            warmup: 60,
+            filter: None,
+            baseline: "benchmark-baseline.ron".into(),
+            update_baseline: false,
+            tolerance: 0.1,
+            tolerances: Vec::new(),
+            higher_is_better: vec![FrameTimeDiagnosticsPlugin::FPS],
+        }
+    }
+}
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(self.clone());
+        app.add_resource(BenchmarkState {
+            frame: 0,
+            history: DiagnosticHistory::new(self.frames),
+            done: false,
+        });
+        app.init_resource::<BenchmarkResult>();
+        app.add_system_to_stage(stage::POST_UPDATE, Self::benchmark_system.system());
+    }
+}
+
+impl BenchmarkPlugin {
+    pub fn new(baseline: impl Into<PathBuf>) -> Self {
+        BenchmarkPlugin {
+            baseline: baseline.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn filtered(filter: Vec<DiagnosticId>, baseline: impl Into<PathBuf>) -> Self {
+        BenchmarkPlugin {
+            filter: Some(filter),
+            ..BenchmarkPlugin::new(baseline)
+        }
+    }
+
+    /// Runs the scenario set up by `scenario` headless, as fast as possible, with frame time
+    /// diagnostics, until the benchmark is done; the report is an `Err` if it failed
+    pub fn run(
+        self,
+        scenario: impl FnOnce(&mut AppBuilder),
+    ) -> Result<BenchmarkReport, BenchmarkReport> {
+        if self.frames == 0 {
+            return Err(BenchmarkReport {
+                frames: 0,
+                baseline: self.baseline,
+                rows: Vec::new(),
+                error: Some("no frames to measure".to_string()),
+                updated_baseline: false,
+            });
+        }
+        let result = Arc::new(Mutex::new(None));
+        let mut app = App::build();
+        app.add_plugin(TypeRegistryPlugin::default())
+            .add_plugin(CorePlugin::default())
+            .add_plugin(DiagnosticsPlugin::default())
+            .add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_plugin(ScheduleRunnerPlugin::run_loop(Duration::from_secs(0)))
+            .add_plugin(self)
+            .add_resource(BenchmarkResult(result.clone()));
+        scenario(&mut app);
+        app.run();
+        let report = result
+            .lock()
+            .unwrap()
+            .take()
+            .expect("the benchmark app exited before the benchmark finished");
+        if report.passed() {
+            Ok(report)
+        } else {
+            Err(report)
+        }
+    }
+
+    fn tolerance(&self, id: DiagnosticId) -> f64 {
+        self.tolerances
+            .iter()
+            .find(|(tolerance_id, _)| *tolerance_id == id)
+            .map_or(self.tolerance, |(_, tolerance)| *tolerance)
+    }
+
+    fn stats(&self, history: &DiagnosticHistory, id: DiagnosticId) -> Option<BenchmarkStats> {
+        let count = history.samples(id).count();
+        if count == 0 {
+            return None;
+        }
+        let percentile = if self.higher_is_better.contains(&id) {
+            5.0
+        } else {
+            95.0
+        };
+        Some(BenchmarkStats {
+            mean: history.samples(id).sum::<f64>() / count as f64,
+            tail: history.percentile(id, percentile)?,
+        })
+    }
+
+    fn report(&self, history: &DiagnosticHistory, diagnostics: &Diagnostics) -> BenchmarkReport {
+        let (baseline, error) = match self.load_baseline() {
+            Ok(baseline) => (baseline, None),
+            Err(_) if self.update_baseline => (BenchmarkBaseline::default(), None),
+            Err(error) => (BenchmarkBaseline::default(), Some(error)),
+        };
+        let mut rows = Vec::new();
+        for id in filtered_ids(self.filter.as_deref(), diagnostics) {
+            let diagnostic = match diagnostics.get(id) {
+                Some(diagnostic) => diagnostic.name.clone(),
+                None => id.0.to_string(),
+            };
+            rows.push(BenchmarkRow {
+                baseline: baseline.get(&diagnostic).cloned(),
+                current: self.stats(history, id),
+                diagnostic,
+                tolerance: self.tolerance(id),
+                higher_is_better: self.higher_is_better.contains(&id),
+            });
+        }
+        BenchmarkReport {
+            frames: self.frames,
+            baseline: self.baseline.clone(),
+            rows,
+            error,
+            updated_baseline: false,
+        }
+    }
+
+    fn load_baseline(&self) -> Result<BenchmarkBaseline, String> {
+        let ron = std::fs::read_to_string(&self.baseline).map_err(|error| {
+            format!(
+                "could not read baseline {} ({}), run with update_baseline to create it",
+                self.baseline.display(),
+                error
+            )
+        })?;
+        ron::de::from_str(&ron).map_err(|error| {
+            format!(
+                "could not parse baseline {}: {}",
+                self.baseline.display(),
+                error
+            )
+        })
+    }
+
+    fn save_baseline(&self, report: &BenchmarkReport) -> Result<(), String> {
+        let baseline = report
+            .rows
+            .iter()
+            .filter_map(|row| Some((row.diagnostic.clone(), row.current?)))
+            .collect::<BenchmarkBaseline>();
+        let ron = ron::ser::to_string_pretty(&baseline, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(&self.baseline, ron).map_err(|error| error.to_string())
+    }
+
+    fn benchmark_system(
+        benchmark: Res<BenchmarkPlugin>,
+        mut state: ResMut<BenchmarkState>,
+        result: Res<BenchmarkResult>,
+        diagnostics: Res<Diagnostics>,
+        mut app_exit_events: ResMut<Events<AppExit>>,
+    ) {
+        if state.done {
+            return;
+        }
+        state.frame += 1;
+        if state.frame > benchmark.warmup {
+            for diagnostic in diagnostics.iter() {
+                if !is_filtered(benchmark.filter.as_deref(), diagnostic.id) {
+                    continue;
+                }
+                if let Some(value) = diagnostic.value() {
+                    state.history.push(diagnostic.id, value);
+                }
+            }
+        }
+        // checked on every frame, warm-up included, so the report is written even without any
+        // frames to measure
+        if state.frame < benchmark.warmup + benchmark.frames {
+            return;
+        }
+        state.done = true;
+        let mut report = benchmark.report(&state.history, &diagnostics);
+        if benchmark.update_baseline {
+            match benchmark.save_baseline(&report) {
+                Ok(()) => report.updated_baseline = true,
+                Err(error) => {
+                    report.error = Some(format!("could not save baseline: {}", error));
+                }
+            }
+        }
+        *result.0.lock().unwrap() = Some(report);
+        app_exit_events.send(AppExit);
+    }
+}
+
+impl BenchmarkRow {
+    /// How much worse `current` is than `baseline`, as a fraction of it, negative if better
+    fn regression(&self, current: f64, baseline: f64) -> f64 {
+        let change = change(current, baseline);
+        if self.higher_is_better {
+            -change
+        } else {
+            change
+        }
+    }
+
+    pub fn regressed(&self) -> bool {
+        match (self.baseline, self.current) {
+            (Some(baseline), Some(current)) => {
+                self.regression(current.mean, baseline.mean) > self.tolerance
+                    || self.regression(current.tail, baseline.tail) > self.tolerance
+            }
+            // measured before, but not any more
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl BenchmarkReport {
+    pub fn regressed(&self) -> bool {
+        self.rows.iter().any(BenchmarkRow::regressed)
+    }
+
+    /// Whether nothing went wrong, and nothing regressed unless the baseline was updated
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && (self.updated_baseline || !self.regressed())
+    }
+}
+
+/// The change from `baseline` to `current` as a fraction of `baseline`, infinite if `baseline`
+/// is 0 and `current` isn't
+fn change(current: f64, baseline: f64) -> f64 {
+    if baseline != 0.0 {
+        (current - baseline) / baseline.abs()
+    } else if current == 0.0 {
+        0.0
+    } else {
+        f64::INFINITY.copysign(current)
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Benchmark of {} frames against {}",
+            self.frames,
+            self.baseline.display()
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:<5} {:>14} {:>14} {:>9} {:>9}",
+            "diagnostic", "stat", "baseline", "current", "change", "tolerance"
+        )?;
+        for row in self.rows.iter() {
+            let status = if row.regressed() { "REGRESSED" } else { "ok" };
+            match (row.baseline, row.current) {
+                (Some(baseline), Some(current)) => {
+                    for (stat, baseline, current) in [
+                        ("mean", baseline.mean, current.mean),
+                        ("tail", baseline.tail, current.tail),
+                    ]
+                    .iter()
+                    {
+                        writeln!(
+                            f,
+                            "{:<24} {:<5} {:>14.6} {:>14.6} {:>+8.1}% {:>8.1}% {}",
+                            row.diagnostic,
+                            stat,
+                            baseline,
+                            current,
+                            change(*current, *baseline) * 100.0,
+                            row.tolerance * 100.0,
+                            status
+                        )?;
+                    }
+                }
+                (None, Some(current)) => writeln!(
+                    f,
+                    "{:<24} {:<5} {:>14} {:>14.6} (new, not compared)",
+                    row.diagnostic, "mean", "-", current.mean
+                )?,
+                (Some(baseline), None) => writeln!(
+                    f,
+                    "{:<24} {:<5} {:>14.6} {:>14} {}",
+                    row.diagnostic, "mean", baseline.mean, "not measured", status
+                )?,
+                (None, None) => writeln!(f, "{:<24} not measured", row.diagnostic)?,
+            }
+        }
+        if let Some(ref error) = self.error {
+            write!(f, "FAILED: {}", error)
+        } else if self.updated_baseline {
+            write!(
+                f,
+                "saved as the new baseline to {}",
+                self.baseline.display()
+            )
+        } else if self.regressed() {
+            write!(f, "FAILED: diagnostics regressed past their tolerance")
+        } else {
+            write!(f, "passed")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(baseline: f64, current: f64, higher_is_better: bool) -> BenchmarkRow {
+        BenchmarkRow {
+            diagnostic: "test".to_string(),
+            baseline: Some(BenchmarkStats {
+                mean: baseline,
+                tail: baseline,
+            }),
+            current: Some(BenchmarkStats {
+                mean: current,
+                tail: current,
+            }),
+            tolerance: 0.1,
+            higher_is_better,
+        }
+    }
+
+    #[test]
+    fn regresses_from_zero() {
+        assert!(row(0.0, 1.0, false).regressed());
+        assert!(row(0.0, -1.0, true).regressed());
+        assert!(!row(0.0, 0.0, false).regressed());
+        assert!(!row(0.0, 1.0, true).regressed());
+    }
+
+    #[test]
+    fn missing_baseline_fails_unless_updating() {
+        let benchmark = BenchmarkPlugin::new("missing-benchmark-baseline.ron");
+        let history = DiagnosticHistory::new(1);
+        let diagnostics = Diagnostics::default();
+        assert!(!benchmark.report(&history, &diagnostics).passed());
+        let benchmark = BenchmarkPlugin {
+            update_baseline: true,
+            ..benchmark
+        };
+        assert!(benchmark.report(&history, &diagnostics).passed());
+    }
+
+    #[test]
+    fn zero_frames_is_rejected() {
+        let benchmark = BenchmarkPlugin {
+            frames: 0,
+            ..BenchmarkPlugin::new("missing-benchmark-baseline.ron")
+        };
+        let report = benchmark.run(|_| {}).unwrap_err();
+        assert_eq!(report.error.as_deref(), Some("no frames to measure"));
+    }
+}
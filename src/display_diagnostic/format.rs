@@ -0,0 +1,116 @@
+use bevy::diagnostic::{DiagnosticId, FrameTimeDiagnosticsPlugin};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How the values of a diagnostic are shown in the overlay
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticFormat {
+    /// Shown after each value, e.g. `ms`
+    pub unit: &'static str,
+    /// Decimal places shown
+    pub precision: usize,
+    /// Multiplies each value before it's shown, e.g. `1000.0` to show seconds as milliseconds
+    pub scale: f64,
+    /// Shows the mean of this many of the latest values, rather than only the latest
+    pub smoothing: usize,
+}
+
+/// The [DiagnosticFormat] of each diagnostic, by id
+#[derive(Debug, Default)]
+pub struct DiagnosticFormats {
+    formats: HashMap<DiagnosticId, DiagnosticFormat>,
+    default: DiagnosticFormat,
+}
+
+impl Default for DiagnosticFormat {
+    fn default() -> Self {
+        DiagnosticFormat {
+            unit: "",
+            precision: 6,
+            scale: 1.0,
+            smoothing: 1,
+        }
+    }
+}
+
+impl DiagnosticFormat {
+    pub fn new(unit: &'static str, precision: usize) -> Self {
+        DiagnosticFormat {
+            unit,
+            precision,
+            ..Default::default()
+        }
+    }
+
+    pub fn scaled(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn smoothed(mut self, smoothing: usize) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        let mut buffer = String::new();
+        self.write(&mut buffer, value);
+        buffer
+    }
+
+    /// Appends the formatted value to `buffer`, without allocating a new string
+    pub fn write(&self, buffer: &mut String, value: f64) {
+        write!(
+            buffer,
+            "{:.*}{}",
+            self.precision,
+            value * self.scale,
+            self.unit
+        )
+        .unwrap();
+    }
+
+    /// Formats for the diagnostics of the [FrameTimeDiagnosticsPlugin]
+    pub fn frame_time_defaults() -> Vec<(DiagnosticId, DiagnosticFormat)> {
+        vec![
+            (
+                FrameTimeDiagnosticsPlugin::FPS,
+                DiagnosticFormat::new("", 1).smoothed(10),
+            ),
+            (
+                FrameTimeDiagnosticsPlugin::FRAME_TIME,
+                DiagnosticFormat::new("ms", 2).scaled(1000.0).smoothed(10),
+            ),
+            (
+                FrameTimeDiagnosticsPlugin::FRAME_COUNT,
+                DiagnosticFormat::new("", 0),
+            ),
+        ]
+    }
+}
+
+impl DiagnosticFormats {
+    pub fn new(formats: Vec<(DiagnosticId, DiagnosticFormat)>) -> Self {
+        DiagnosticFormats {
+            formats: formats.into_iter().collect(),
+            default: DiagnosticFormat::default(),
+        }
+    }
+
+    /// The format of `id`, or the default format if it has none
+    pub fn get(&self, id: DiagnosticId) -> &DiagnosticFormat {
+        self.formats.get(&id).unwrap_or(&self.default)
+    }
+
+    pub fn set(&mut self, id: DiagnosticId, format: DiagnosticFormat) {
+        self.formats.insert(id, format);
+    }
+
+    /// The longest smoothing window of any format
+    pub(super) fn max_smoothing(&self) -> usize {
+        self.formats
+            .values()
+            .map(|format| format.smoothing)
+            .fold(self.default.smoothing, usize::max)
+    }
+}
@@ -0,0 +1,183 @@
+use crate::font::FontMap;
+use crate::leak;
+
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::ecs::Component;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::Instant;
+
+/// An App Plugin that measures the number of entities
+#[derive(Default)]
+pub struct EntityCountDiagnosticsPlugin;
+
+/// An App Plugin that measures how long each of `stages` takes to run, in seconds
+///
+/// Each stage is timed from a stage added just before it to one added just after it, so stages
+/// added next to it later on are counted with it. Timing individual systems is out of scope, as
+/// bevy runs a stage's systems without hooks around each one.
+pub struct StageTimeDiagnosticsPlugin {
+    pub stages: Vec<&'static str>,
+}
+
+/// An App Plugin that measures how many of the fonts in the [FontMap] resource are loaded
+#[derive(Default)]
+pub struct FontDiagnosticsPlugin;
+
+/// An App Plugin that measures the number of entities with a `T` component
+pub struct ComponentCountDiagnosticsPlugin<T> {
+    pub id: DiagnosticId,
+    pub name: &'static str,
+    _marker: PhantomData<fn(T)>,
+}
+
+/// When each timed stage last started
+#[derive(Default)]
+struct StageTimers(HashMap<&'static str, Instant>);
+
+impl Plugin for EntityCountDiagnosticsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(Self::setup_system.system());
+        app.add_system(Self::diagnostic_system.system());
+    }
+}
+
+impl EntityCountDiagnosticsPlugin {
+    pub const ENTITY_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(0x9a6865d3443f4cd19266a9ca9761bb9f);
+
+    fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        diagnostics.add(Diagnostic::new(Self::ENTITY_COUNT, "entity_count", 20));
+    }
+
+    fn diagnostic_system(mut diagnostics: ResMut<Diagnostics>, mut query: Query<Entity>) {
+        let count = query.iter().iter().count();
+        diagnostics.add_measurement(Self::ENTITY_COUNT, count as f64);
+    }
+}
+
+impl Default for StageTimeDiagnosticsPlugin {
+    fn default() -> Self {
+        StageTimeDiagnosticsPlugin {
+            stages: vec![stage::PRE_UPDATE, stage::UPDATE, stage::POST_UPDATE],
+        }
+    }
+}
+
+impl Plugin for StageTimeDiagnosticsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<StageTimers>();
+        let stages = self.stages.clone();
+        app.add_startup_system(
+            (move |mut diagnostics: ResMut<Diagnostics>| {
+                for stage in stages.iter() {
+                    let name = format!("{}_time", stage);
+                    diagnostics.add(Diagnostic::new(Self::diagnostic_id(stage), &name, 20));
+                }
+            })
+            .system(),
+        );
+        for &stage in self.stages.iter() {
+            let start = leak(format!("{}_timing_start", stage));
+            let end = leak(format!("{}_timing_end", stage));
+            app.add_stage_before(stage, start)
+                .add_stage_after(stage, end)
+                .add_system_to_stage(
+                    start,
+                    (move |_: &mut World, resources: &mut Resources| {
+                        let mut timers = resources.get_mut::<StageTimers>().unwrap();
+                        timers.0.insert(stage, Instant::now());
+                    })
+                    .thread_local_system(),
+                )
+                .add_system_to_stage(
+                    end,
+                    (move |_: &mut World, resources: &mut Resources| {
+                        let started = resources
+                            .get::<StageTimers>()
+                            .unwrap()
+                            .0
+                            .get(stage)
+                            .cloned();
+                        if let Some(started) = started {
+                            let mut diagnostics = resources.get_mut::<Diagnostics>().unwrap();
+                            diagnostics.add_measurement(
+                                Self::diagnostic_id(stage),
+                                started.elapsed().as_secs_f64(),
+                            );
+                        }
+                    })
+                    .thread_local_system(),
+                );
+        }
+    }
+}
+
+impl StageTimeDiagnosticsPlugin {
+    /// The id of the time taken by `stage`, the same in every run
+    pub fn diagnostic_id(stage: &str) -> DiagnosticId {
+        // FNV-1a, as std's hashers aren't guaranteed to be stable
+        let hash = stage.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        DiagnosticId::from_u128(0x3cd2338ea2dd435baa502dfd997bb444 ^ hash as u128)
+    }
+}
+
+impl Plugin for FontDiagnosticsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(Self::setup_system.system());
+        app.add_system(Self::diagnostic_system.system());
+    }
+}
+
+impl FontDiagnosticsPlugin {
+    pub const FONTS_LOADED: DiagnosticId =
+        DiagnosticId::from_u128(0x02dbec95285d4701b92728292bebace8);
+
+    fn setup_system(mut diagnostics: ResMut<Diagnostics>) {
+        diagnostics.add(Diagnostic::new(Self::FONTS_LOADED, "fonts_loaded", 20));
+    }
+
+    fn diagnostic_system(
+        mut diagnostics: ResMut<Diagnostics>,
+        font_map: Res<FontMap>,
+        fonts: Res<Assets<Font>>,
+    ) {
+        let loaded = font_map
+            .handles()
+            .filter(|handle| fonts.get(*handle).is_some())
+            .count();
+        diagnostics.add_measurement(Self::FONTS_LOADED, loaded as f64);
+    }
+}
+
+impl<T: Component> ComponentCountDiagnosticsPlugin<T> {
+    pub fn new(id: DiagnosticId, name: &'static str) -> Self {
+        ComponentCountDiagnosticsPlugin {
+            id,
+            name,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> Plugin for ComponentCountDiagnosticsPlugin<T> {
+    fn build(&self, app: &mut AppBuilder) {
+        let (id, name) = (self.id, self.name);
+        app.add_startup_system(
+            (move |mut diagnostics: ResMut<Diagnostics>| {
+                diagnostics.add(Diagnostic::new(id, name, 20));
+            })
+            .system(),
+        );
+        app.add_system(
+            (move |mut diagnostics: ResMut<Diagnostics>, mut query: Query<&T>| {
+                let count = query.iter().iter().count();
+                diagnostics.add_measurement(id, count as f64);
+            })
+            .system(),
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use super::{DisplayDiagnosticsState, DisplayDiagnosticsStyle};
+
+use bevy::diagnostic::{DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use bevy::render::mesh::{VertexAttribute, VertexAttributeValues};
+use bevy::render::pipeline::PrimitiveTopology;
+use std::collections::{HashMap, VecDeque};
+
+/// The look of the rolling graphs drawn under the overlay's text, one per displayed diagnostic
+///
+/// Each graph's bars and markers are the quads of a mesh, moved in place every frame, so the
+/// graphs don't make the UI lay itself out again.
+#[derive(Debug, Clone)]
+pub struct DiagnosticsGraphStyle {
+    /// How many of the most recent values are drawn, one bar each
+    pub samples: usize,
+    pub bar_width: f32,
+    pub height: f32,
+    /// Most graphs drawn; diagnostics past this are only shown as text
+    pub max_graphs: usize,
+    pub color: Color,
+    /// The percentile marked on each graph, between 0 and 100
+    pub percentile: f64,
+    pub min_color: Color,
+    pub max_color: Color,
+    pub percentile_color: Color,
+}
+
+/// The values of each displayed diagnostic over the last frames, oldest first
+///
+/// There's one sample per frame, so a diagnostic measured less often than once a frame repeats
+/// its latest value until it's measured again, weighting its min, max and percentile towards the
+/// values it held for longest.
+#[derive(Debug, Default)]
+pub struct DiagnosticHistory {
+    capacity: usize,
+    samples: HashMap<DiagnosticId, VecDeque<f64>>,
+}
+
+/// The values drawn by each graph, reused from one frame to the next
+#[derive(Default)]
+pub(super) struct GraphBuffers {
+    graphs: Vec<GraphValues>,
+    /// How many of `graphs` are drawn this frame
+    count: usize,
+}
+
+#[derive(Default)]
+struct GraphValues {
+    samples: Vec<f64>,
+    /// `samples` sorted, for the min, max and percentile
+    sorted: Vec<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    percentile: Option<f64>,
+    scale: f64,
+}
+
+/// One of the nodes making up the `graph`th graph
+pub(super) struct DisplayGraphNode {
+    graph: usize,
+    part: GraphPart,
+}
+
+pub(super) enum GraphPart {
+    Graph,
+    /// A bar per sample, the oldest being the leftmost
+    Bars,
+    Marker(GraphMarker),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum GraphMarker {
+    Min,
+    Max,
+    Percentile,
+}
+
+impl Default for DiagnosticsGraphStyle {
+    fn default() -> Self {
+        DiagnosticsGraphStyle {
+            samples: 120,
+            bar_width: 1.0,
+            height: 24.0,
+            max_graphs: 4,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+            percentile: 95.0,
+            min_color: Color::rgb(0.0, 1.0, 0.0),
+            max_color: Color::rgb(1.0, 0.0, 0.0),
+            percentile_color: Color::rgb(1.0, 1.0, 0.0),
+        }
+    }
+}
+
+impl DiagnosticHistory {
+    pub fn new(capacity: usize) -> Self {
+        DiagnosticHistory {
+            capacity,
+            samples: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, id: DiagnosticId, value: f64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let capacity = self.capacity;
+        let samples = self
+            .samples
+            .entry(id)
+            .or_insert_with(|| VecDeque::with_capacity(capacity));
+        if samples.len() == capacity {
+            samples.pop_front();
+        }
+        samples.push_back(value);
+    }
+
+    pub fn samples(&self, id: DiagnosticId) -> impl Iterator<Item = &f64> {
+        self.samples.get(&id).into_iter().flatten()
+    }
+
+    /// The last `count` values of `id`, oldest first
+    pub fn recent(&self, id: DiagnosticId, count: usize) -> impl Iterator<Item = f64> + '_ {
+        let samples = self.samples.get(&id);
+        let skip = samples.map_or(0, |samples| samples.len().saturating_sub(count));
+        samples.into_iter().flatten().skip(skip).cloned()
+    }
+
+    /// The mean of the last `count` values of `id`
+    pub fn mean(&self, id: DiagnosticId, count: usize) -> Option<f64> {
+        let (len, sum) = self
+            .recent(id, count)
+            .fold((0, 0.0), |(len, sum), value| (len + 1, sum + value));
+        if len == 0 {
+            None
+        } else {
+            Some(sum / len as f64)
+        }
+    }
+
+    pub fn min(&self, id: DiagnosticId) -> Option<f64> {
+        min(self.samples(id).cloned())
+    }
+
+    pub fn max(&self, id: DiagnosticId) -> Option<f64> {
+        max(self.samples(id).cloned())
+    }
+
+    /// The nearest-rank `percentile` (between 0 and 100) of the recorded values
+    pub fn percentile(&self, id: DiagnosticId, percentile: f64) -> Option<f64> {
+        let mut values = self.samples(id).cloned().collect::<Vec<_>>();
+        nearest_rank(&mut values, percentile)
+    }
+}
+
+fn min(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.fold(None, |min, value| match min {
+        Some(min) if min <= value => Some(min),
+        _ => Some(value),
+    })
+}
+
+fn max(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.fold(None, |max, value| match max {
+        Some(max) if max >= value => Some(max),
+        _ => Some(value),
+    })
+}
+
+/// Sorts `values` in place, and picks their nearest-rank `percentile`
+fn nearest_rank(values: &mut [f64], percentile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+    Some(values[rank.max(1).min(values.len()) - 1])
+}
+
+/// Spawns the (initially hidden) graphs as a child of `panel`
+pub(super) fn spawn_graphs(
+    panel: &mut ChildBuilder,
+    materials: &mut Assets<ColorMaterial>,
+    meshes: &mut Assets<Mesh>,
+    transparent: Handle<ColorMaterial>,
+    style: &DiagnosticsGraphStyle,
+) {
+    let bar = materials.add(style.color.into());
+    let markers = [
+        (GraphMarker::Min, materials.add(style.min_color.into())),
+        (GraphMarker::Max, materials.add(style.max_color.into())),
+        (
+            GraphMarker::Percentile,
+            materials.add(style.percentile_color.into()),
+        ),
+    ];
+    // the meshes cover the whole graph, and are stretched to its size
+    let layer = Style {
+        position_type: PositionType::Absolute,
+        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+        ..Default::default()
+    };
+    panel
+        .spawn(NodeComponents {
+            style: Style {
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: transparent.clone(),
+            ..Default::default()
+        })
+        .with_children(|graphs| {
+            for graph in 0..style.max_graphs {
+                graphs
+                    .spawn(NodeComponents {
+                        style: Style {
+                            display: Display::None,
+                            size: Size::new(
+                                Val::Px(style.samples as f32 * style.bar_width),
+                                Val::Px(style.height),
+                            ),
+                            margin: Rect {
+                                top: Val::Px(1.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        material: transparent.clone(),
+                        ..Default::default()
+                    })
+                    .with(DisplayGraphNode {
+                        graph,
+                        part: GraphPart::Graph,
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn(NodeComponents {
+                                style: layer.clone(),
+                                mesh: meshes.add(quads_mesh(style.samples)),
+                                material: bar.clone(),
+                                ..Default::default()
+                            })
+                            .with(DisplayGraphNode {
+                                graph,
+                                part: GraphPart::Bars,
+                            });
+                        for (marker, material) in markers.iter() {
+                            parent
+                                .spawn(NodeComponents {
+                                    style: layer.clone(),
+                                    mesh: meshes.add(quads_mesh(1)),
+                                    material: material.clone(),
+                                    ..Default::default()
+                                })
+                                .with(DisplayGraphNode {
+                                    graph,
+                                    part: GraphPart::Marker(*marker),
+                                });
+                        }
+                    });
+            }
+        });
+}
+
+/// A mesh of `quads` quads, all empty until [set_quad] moves their corners
+fn quads_mesh(quads: usize) -> Mesh {
+    let vertices = quads * 4;
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    // the position is first, for set_quad
+    mesh.attributes
+        .push(VertexAttribute::position(vec![[0.0; 3]; vertices]));
+    mesh.attributes
+        .push(VertexAttribute::normal(vec![[0.0, 0.0, 1.0]; vertices]));
+    mesh.attributes
+        .push(VertexAttribute::uv(vec![[0.0; 2]; vertices]));
+    mesh.indices = Some(
+        (0..quads as u32)
+            .flat_map(|quad| [0, 1, 2, 0, 2, 3].iter().map(move |i| quad * 4 + i))
+            .collect(),
+    );
+    mesh
+}
+
+/// Moves the corners of the `quad`th quad of `mesh`, in units of the node's size with the
+/// origin at its centre
+fn set_quad(mesh: &mut Mesh, quad: usize, left: f32, bottom: f32, right: f32, top: f32) {
+    if let Some(VertexAttributeValues::Float3(positions)) = mesh
+        .attributes
+        .get_mut(0)
+        .map(|attribute| &mut attribute.values)
+    {
+        // anticlockwise, facing the camera
+        let corners = [[left, bottom], [right, bottom], [right, top], [left, top]];
+        for (position, [x, y]) in positions[quad * 4..quad * 4 + 4].iter_mut().zip(&corners) {
+            *position = [*x, *y, 0.0];
+        }
+    }
+}
+
+/// Records this frame's value of each displayed diagnostic, whether or not it was measured again
+/// this frame
+pub(super) fn diagnostic_history_system(
+    state: Res<DisplayDiagnosticsState>,
+    diagnostics: Res<Diagnostics>,
+    mut history: ResMut<DiagnosticHistory>,
+) {
+    for id in state.displayed(&diagnostics) {
+        if let Some(value) = diagnostics.get(id).and_then(|d| d.value()) {
+            history.push(id, value);
+        }
+    }
+}
+
+/// Collects the samples, min, max and percentile drawn by the graph of each displayed diagnostic
+pub(super) fn graph_values_system(
+    state: Res<DisplayDiagnosticsState>,
+    style: Res<DisplayDiagnosticsStyle>,
+    diagnostics: Res<Diagnostics>,
+    history: Res<DiagnosticHistory>,
+    mut buffers: ResMut<GraphBuffers>,
+) {
+    let graph_style = match style.graph {
+        Some(ref graph_style) => graph_style,
+        None => return,
+    };
+    let buffers = &mut *buffers;
+    buffers.count = 0;
+    for id in state
+        .displayed(&diagnostics)
+        .filter(|id| diagnostics.get(*id).is_some())
+        .take(graph_style.max_graphs)
+    {
+        if buffers.graphs.len() == buffers.count {
+            buffers.graphs.push(GraphValues::default());
+        }
+        let values = &mut buffers.graphs[buffers.count];
+        values.samples.clear();
+        values
+            .samples
+            .extend(history.recent(id, graph_style.samples));
+        values.sorted.clear();
+        values.sorted.extend_from_slice(&values.samples);
+        values.percentile = nearest_rank(&mut values.sorted, graph_style.percentile);
+        values.min = values.sorted.first().cloned();
+        values.max = values.sorted.last().cloned();
+        // leaves some headroom above the highest value
+        values.scale = values.max.unwrap_or(0.0) * 1.25;
+        buffers.count += 1;
+    }
+}
+
+/// Redraws the graph of each displayed diagnostic, moving the quads of its meshes
+pub(super) fn diagnostics_graph_system(
+    style: Res<DisplayDiagnosticsStyle>,
+    buffers: Res<GraphBuffers>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut graph_query: Query<(&DisplayGraphNode, &mut Style)>,
+    mut mesh_query: Query<(&DisplayGraphNode, &Handle<Mesh>)>,
+) {
+    let graph_style = match style.graph {
+        Some(ref graph_style) => graph_style,
+        None => return,
+    };
+    let graphs = &buffers.graphs[..buffers.count];
+
+    // only shown and hidden as diagnostics come and go, as that lays the UI out again
+    for (node, mut node_style) in &mut graph_query.iter() {
+        if let GraphPart::Graph = node.part {
+            let display = if node.graph < graphs.len() {
+                Display::Flex
+            } else {
+                Display::None
+            };
+            if node_style.display != display {
+                node_style.display = display;
+            }
+        }
+    }
+
+    // the bottom of the graph is at -0.5 and the top at 0.5
+    let height = |value: f64, scale: f64| {
+        if scale > 0.0 {
+            ((value / scale) as f32).max(0.0).min(1.0) - 0.5
+        } else {
+            -0.5
+        }
+    };
+    let samples = graph_style.samples;
+    for (node, mesh) in &mut mesh_query.iter() {
+        // the graph itself is drawn with the shared quad mesh, left untouched
+        if let GraphPart::Graph = node.part {
+            continue;
+        }
+        let values = match graphs.get(node.graph) {
+            Some(values) => values,
+            None => continue,
+        };
+        let mesh = match meshes.get_mut(mesh) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        match node.part {
+            GraphPart::Graph => {}
+            GraphPart::Bars => {
+                // samples fill the graph from the right, the newest being the last bar
+                let offset = samples.saturating_sub(values.samples.len());
+                for bar in 0..samples {
+                    let top = match bar.checked_sub(offset) {
+                        Some(index) => height(values.samples[index], values.scale),
+                        None => -0.5,
+                    };
+                    let left = bar as f32 / samples as f32 - 0.5;
+                    let right = (bar + 1) as f32 / samples as f32 - 0.5;
+                    set_quad(mesh, bar, left, -0.5, right, top);
+                }
+            }
+            GraphPart::Marker(marker) => {
+                let value = match marker {
+                    GraphMarker::Min => values.min,
+                    GraphMarker::Max => values.max,
+                    GraphMarker::Percentile => values.percentile,
+                };
+                let bottom = height(value.unwrap_or(0.0), values.scale);
+                // a line one pixel thick
+                let top = bottom + 1.0 / graph_style.height;
+                set_quad(mesh, 0, -0.5, bottom, 0.5, top);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: DiagnosticId = DiagnosticId::from_u128(0x5d6f1c2e8b9a4c7d9e0f1a2b3c4d5e6f);
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = DiagnosticHistory::new(2);
+        for value in 0..5 {
+            history.push(ID, value as f64);
+        }
+        assert_eq!(
+            history.samples(ID).cloned().collect::<Vec<_>>(),
+            vec![3.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut history = DiagnosticHistory::new(0);
+        for value in 0..5 {
+            history.push(ID, value as f64);
+        }
+        assert_eq!(history.samples(ID).count(), 0);
+        assert_eq!(history.mean(ID, 10), None);
+    }
+}
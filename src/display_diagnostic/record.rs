@@ -0,0 +1,294 @@
+use super::{filtered_ids, DisplayDiagnosticsState};
+
+use bevy::diagnostic::{DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// How a [DiagnosticsRecorder] writes its samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// A `time,frame,diagnostic,value,average` header, then one row per sample
+    Csv,
+    /// One JSON object per sample and line
+    JsonLines,
+}
+
+/// Where and how the [DiagnosticsRecorder] records
+#[derive(Debug, Clone)]
+pub struct RecordSettings {
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    /// Starts or stops recording when pressed
+    pub toggle_key: Option<KeyCode>,
+    /// Starts recording as soon as the app starts
+    pub start: bool,
+}
+
+/// Records every frame's value of each (filtered) diagnostic to a file, between `start` and `stop`
+pub struct DiagnosticsRecorder {
+    settings: RecordSettings,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    writer: BufWriter<File>,
+    format: RecordFormat,
+    /// The time of the first sample, which the recorded times are relative to
+    started: Option<f64>,
+    frame: u64,
+}
+
+#[derive(Serialize)]
+struct Sample<'a> {
+    time: f64,
+    frame: u64,
+    diagnostic: &'a str,
+    value: f64,
+    average: Option<f64>,
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        RecordSettings {
+            path: "diagnostics.csv".into(),
+            format: RecordFormat::Csv,
+            toggle_key: None,
+            start: false,
+        }
+    }
+}
+
+impl DiagnosticsRecorder {
+    pub fn new(settings: RecordSettings) -> Self {
+        DiagnosticsRecorder {
+            settings,
+            recording: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Starts recording to the configured file, replacing it
+    pub fn start(&mut self) -> io::Result<()> {
+        let path = self.settings.path.clone();
+        let format = self.settings.format;
+        self.start_to(path, format)
+    }
+
+    /// Starts recording to `path`, replacing it, and stopping any recording in progress
+    pub fn start_to(&mut self, path: impl Into<PathBuf>, format: RecordFormat) -> io::Result<()> {
+        self.stop()?;
+        let mut writer = BufWriter::new(File::create(path.into())?);
+        if format == RecordFormat::Csv {
+            writeln!(writer, "time,frame,diagnostic,value,average")?;
+        }
+        self.recording = Some(Recording {
+            writer,
+            format,
+            started: None,
+            frame: 0,
+        });
+        Ok(())
+    }
+
+    /// Stops recording, flushing what was recorded to the file
+    pub fn stop(&mut self) -> io::Result<()> {
+        match self.recording.take() {
+            Some(mut recording) => recording.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn toggle(&mut self) -> io::Result<()> {
+        if self.is_recording() {
+            self.stop()
+        } else {
+            self.start()
+        }
+    }
+}
+
+impl Recording {
+    fn record(
+        &mut self,
+        time: f64,
+        diagnostics: &Diagnostics,
+        ids: &[DiagnosticId],
+    ) -> io::Result<()> {
+        let started = *self.started.get_or_insert(time);
+        for diagnostic in ids.iter().filter_map(|id| diagnostics.get(*id)) {
+            let value = match diagnostic.value() {
+                Some(value) => value,
+                None => continue,
+            };
+            let sample = Sample {
+                time: time - started,
+                frame: self.frame,
+                diagnostic: &diagnostic.name,
+                value,
+                average: diagnostic.average(),
+            };
+            sample.write(&mut self.writer, self.format)?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+impl Sample<'_> {
+    /// Writes the sample as a line of `format`, the average left empty if there's none
+    fn write(&self, writer: &mut impl Write, format: RecordFormat) -> io::Result<()> {
+        match format {
+            RecordFormat::Csv => writeln!(
+                writer,
+                "{},{},{},{},{}",
+                self.time,
+                self.frame,
+                csv_field(self.diagnostic),
+                self.value,
+                self.average.map_or(String::new(), |a| a.to_string())
+            ),
+            RecordFormat::JsonLines => {
+                serde_json::to_writer(&mut *writer, self)?;
+                writeln!(writer)
+            }
+        }
+    }
+}
+
+/// Quotes a CSV field if it needs to be
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub(super) fn start_recording_system(mut recorder: ResMut<DiagnosticsRecorder>) {
+    if recorder.settings.start {
+        if let Err(error) = recorder.start() {
+            eprintln!("Could not start recording diagnostics: {}", error);
+        }
+    }
+}
+
+pub(super) fn record_diagnostics_system(
+    mut recorder: ResMut<DiagnosticsRecorder>,
+    state: Res<DisplayDiagnosticsState>,
+    time: Res<Time>,
+    diagnostics: Res<Diagnostics>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if let Some(key) = recorder.settings.toggle_key {
+        if keyboard_input.just_pressed(key) {
+            if let Err(error) = recorder.toggle() {
+                eprintln!("Could not start or stop recording diagnostics: {}", error);
+            }
+        }
+    }
+    if let Some(ref mut recording) = recorder.recording {
+        let ids = filtered_ids(state.filter(), &diagnostics).collect::<Vec<_>>();
+        if let Err(error) = recording.record(time.seconds_since_startup, &diagnostics, &ids) {
+            eprintln!("Could not record diagnostics, stopping: {}", error);
+            recorder.recording = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::diagnostic::Diagnostic;
+
+    const FPS: DiagnosticId = DiagnosticId::from_u128(0x7a1e9c3b5d2f4e6a8b0c1d2e3f4a5b6c);
+
+    fn sample(diagnostic: &str, average: Option<f64>) -> Sample<'_> {
+        Sample {
+            time: 0.5,
+            frame: 3,
+            diagnostic,
+            value: 60.0,
+            average,
+        }
+    }
+
+    fn written(sample: &Sample, format: RecordFormat) -> String {
+        let mut line = Vec::new();
+        sample.write(&mut line, format).unwrap();
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("fps"), "fps");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_average_is_empty_without_one() {
+        assert_eq!(
+            written(&sample("fps", Some(59.5)), RecordFormat::Csv),
+            "0.5,3,fps,60,59.5\n"
+        );
+        assert_eq!(
+            written(&sample("frame, time", None), RecordFormat::Csv),
+            "0.5,3,\"frame, time\",60,\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_are_one_object_each() {
+        let line = written(&sample("fps", None), RecordFormat::JsonLines);
+        assert!(line.ends_with('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "time": 0.5,
+                "frame": 3,
+                "diagnostic": "fps",
+                "value": 60.0,
+                "average": null,
+            })
+        );
+    }
+
+    #[test]
+    fn restarting_replaces_the_file() {
+        let path = std::env::temp_dir().join(format!("diagnostics-{}.csv", std::process::id()));
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.add(Diagnostic::new(FPS, "fps", 20));
+        diagnostics.add_measurement(FPS, 60.0);
+        let mut recorder = DiagnosticsRecorder::new(RecordSettings {
+            path: path.clone(),
+            ..Default::default()
+        });
+
+        recorder.start().unwrap();
+        for frame in 0..2 {
+            let recording = recorder.recording.as_mut().unwrap();
+            recording
+                .record(frame as f64, &diagnostics, &[FPS])
+                .unwrap();
+        }
+        recorder.stop().unwrap();
+        assert!(!recorder.is_recording());
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(recorded.lines().count(), 3);
+        assert!(recorded.lines().nth(2).unwrap().starts_with("1,1,fps,60,"));
+
+        recorder.start().unwrap();
+        recorder.stop().unwrap();
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(recorded, "time,frame,diagnostic,value,average\n");
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::diagnostic::{DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// How far past its [Threshold] a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThresholdLevel {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Warning and critical values for a diagnostic, e.g. a frame time over 16.7ms
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub id: DiagnosticId,
+    pub warning: f64,
+    pub critical: f64,
+    /// Whether values under the thresholds, rather than over them, are past them
+    pub below: bool,
+    /// Sends a [ThresholdEvent] when the diagnostic goes past a threshold
+    pub event: bool,
+}
+
+/// Sent when a diagnostic goes past one of its thresholds, if the [Threshold] asks for it
+#[derive(Debug, Clone)]
+pub struct ThresholdEvent {
+    pub id: DiagnosticId,
+    pub level: ThresholdLevel,
+    pub value: f64,
+    pub frame: u64,
+}
+
+/// The thresholds checked every frame, and the level each diagnostic was last at
+#[derive(Debug, Default)]
+pub struct DiagnosticThresholds {
+    thresholds: Vec<Threshold>,
+    levels: HashMap<DiagnosticId, ThresholdLevel>,
+    frame: u64,
+}
+
+impl Threshold {
+    pub fn above(id: DiagnosticId, warning: f64, critical: f64) -> Self {
+        Threshold {
+            id,
+            warning,
+            critical,
+            below: false,
+            event: false,
+        }
+    }
+
+    pub fn below(id: DiagnosticId, warning: f64, critical: f64) -> Self {
+        Threshold {
+            below: true,
+            ..Self::above(id, warning, critical)
+        }
+    }
+
+    pub fn with_event(mut self) -> Self {
+        self.event = true;
+        self
+    }
+
+    pub fn level(&self, value: f64) -> ThresholdLevel {
+        let past = |threshold: f64| {
+            if self.below {
+                value < threshold
+            } else {
+                value > threshold
+            }
+        };
+        if past(self.critical) {
+            ThresholdLevel::Critical
+        } else if past(self.warning) {
+            ThresholdLevel::Warning
+        } else {
+            ThresholdLevel::Normal
+        }
+    }
+}
+
+impl DiagnosticThresholds {
+    pub fn new(thresholds: Vec<Threshold>) -> Self {
+        DiagnosticThresholds {
+            thresholds,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a threshold, replacing any other for the same diagnostic
+    pub fn set(&mut self, threshold: Threshold) {
+        self.remove(threshold.id);
+        self.thresholds.push(threshold);
+    }
+
+    pub fn remove(&mut self, id: DiagnosticId) {
+        self.thresholds.retain(|threshold| threshold.id != id);
+        self.levels.remove(&id);
+    }
+
+    /// The level `id` was at when last checked
+    pub fn level(&self, id: DiagnosticId) -> ThresholdLevel {
+        self.levels
+            .get(&id)
+            .cloned()
+            .unwrap_or(ThresholdLevel::Normal)
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+/// Checks every threshold, warning when a diagnostic goes past one
+pub(super) fn check_thresholds_system(
+    mut thresholds: ResMut<DiagnosticThresholds>,
+    diagnostics: Res<Diagnostics>,
+    mut events: ResMut<Events<ThresholdEvent>>,
+) {
+    thresholds.frame += 1;
+    let frame = thresholds.frame;
+    let mut levels = Vec::new();
+    for threshold in thresholds.thresholds.iter() {
+        let diagnostic = match diagnostics.get(threshold.id) {
+            Some(diagnostic) => diagnostic,
+            None => continue,
+        };
+        if let Some(value) = diagnostic.value() {
+            let level = threshold.level(value);
+            if level > thresholds.level(threshold.id) {
+                eprintln!(
+                    "Frame {}: {} is {:?} at {} (warning {}, critical {})",
+                    frame, diagnostic.name, level, value, threshold.warning, threshold.critical
+                );
+                if threshold.event {
+                    events.send(ThresholdEvent {
+                        id: threshold.id,
+                        level,
+                        value,
+                        frame,
+                    });
+                }
+            }
+            levels.push((threshold.id, level));
+        }
+    }
+    thresholds.levels.extend(levels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::diagnostic::Diagnostic;
+    use bevy::ecs::System;
+
+    const FRAME_TIME: DiagnosticId = DiagnosticId::from_u128(0x5c1d9e2a7b3f4c8d9e0a1b2c3d4e5f60);
+
+    #[test]
+    fn levels_above_and_below() {
+        let above = Threshold::above(FRAME_TIME, 10.0, 20.0);
+        assert_eq!(above.level(5.0), ThresholdLevel::Normal);
+        assert_eq!(above.level(10.0), ThresholdLevel::Normal);
+        assert_eq!(above.level(15.0), ThresholdLevel::Warning);
+        assert_eq!(above.level(25.0), ThresholdLevel::Critical);
+        let below = Threshold::below(FRAME_TIME, 30.0, 20.0);
+        assert_eq!(below.level(40.0), ThresholdLevel::Normal);
+        assert_eq!(below.level(30.0), ThresholdLevel::Normal);
+        assert_eq!(below.level(25.0), ThresholdLevel::Warning);
+        assert_eq!(below.level(15.0), ThresholdLevel::Critical);
+    }
+
+    #[test]
+    fn critical_takes_priority_over_warning() {
+        // a value past both thresholds is critical, even when they're swapped
+        assert_eq!(
+            Threshold::above(FRAME_TIME, 10.0, 20.0).level(30.0),
+            ThresholdLevel::Critical
+        );
+        assert_eq!(
+            Threshold::above(FRAME_TIME, 20.0, 10.0).level(15.0),
+            ThresholdLevel::Critical
+        );
+    }
+
+    #[test]
+    fn events_are_sent_only_when_the_level_rises() {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.add(Diagnostic::new(FRAME_TIME, "frame_time", 1));
+        resources.insert(diagnostics);
+        resources.insert(DiagnosticThresholds::new(vec![Threshold::above(
+            FRAME_TIME, 10.0, 20.0,
+        )
+        .with_event()]));
+        resources.insert(Events::<ThresholdEvent>::default());
+        let mut reader = resources
+            .get::<Events<ThresholdEvent>>()
+            .unwrap()
+            .get_reader();
+        let mut system = check_thresholds_system.system();
+        system.initialize(&mut resources);
+        let mut sent = Vec::new();
+        for &value in &[5.0, 15.0, 18.0, 25.0, 30.0, 5.0, 15.0] {
+            resources
+                .get_mut::<Diagnostics>()
+                .unwrap()
+                .add_measurement(FRAME_TIME, value);
+            system.update_archetype_access(&world);
+            system.run(&mut world, &mut resources);
+            system.run_thread_local(&mut world, &mut resources);
+            let events = resources.get::<Events<ThresholdEvent>>().unwrap();
+            sent.extend(
+                reader
+                    .iter(&events)
+                    .map(|event| (event.frame, event.level, event.value)),
+            );
+        }
+        assert_eq!(
+            sent,
+            vec![
+                (2, ThresholdLevel::Warning, 15.0),
+                (4, ThresholdLevel::Critical, 25.0),
+                (7, ThresholdLevel::Warning, 15.0),
+            ]
+        );
+        let thresholds = resources.get::<DiagnosticThresholds>().unwrap();
+        assert_eq!(thresholds.level(FRAME_TIME), ThresholdLevel::Warning);
+        assert_eq!(thresholds.frame(), 7);
+    }
+}
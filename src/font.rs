@@ -1,27 +1,326 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use bevy::prelude::*;
 
-#[derive(Default)]
+mod registry;
+
+pub use registry::FontRegistry;
+
 pub struct FontMap {
-    fonts: HashMap<&'static str, Handle<Font>>,
+    fonts: HashMap<String, FontUse>,
+    faces: Vec<FontFace>,
+    /// The family used when a requested family has no faces
+    pub fallback_family: &'static str,
+}
+
+/// Starts loading font assets, e.g. the [AssetServer]
+///
+/// The [FontMap] only calls this for paths it doesn't hold yet.
+pub trait LoadFont {
+    fn load_font(&self, path: &str) -> Handle<Font>;
+}
+
+/// A loaded font, and how many users have asked for it without unloading it since
+struct FontUse {
+    handle: Handle<Font>,
+    users: usize,
+}
+
+/// A font kept loaded by the [FontMap], from [FontMap::resident]
+#[derive(Debug, Clone)]
+pub struct ResidentFont {
+    pub path: String,
+    pub users: usize,
+    /// Whether the font has finished loading
+    pub loaded: bool,
+}
+
+/// Every font kept loaded by the [FontMap], by path
+#[derive(Debug, Clone, Default)]
+pub struct ResidentFonts(pub Vec<ResidentFont>);
+
+/// How heavy a font face is, from 100 to 900 as in CSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weight {
+    Thin = 100,
+    ExtraLight = 200,
+    Light = 300,
+    SemiLight = 350,
+    Regular = 400,
+    Medium = 500,
+    SemiBold = 600,
+    Bold = 700,
+    ExtraBold = 800,
+    Black = 900,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+}
+
+/// A font file, and the family, weight and style it provides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontFace {
+    pub family: &'static str,
+    pub weight: Weight,
+    pub style: FontStyle,
+    pub path: &'static str,
 }
 
 impl FontMap {
-    pub fn get_or_load(
+    /// A map of the faces [discovered](FontRegistry::discover) in `directory`, warning about any
+    /// that couldn't be read or have no license
+    pub fn discover(directory: &str) -> Self {
+        let faces = match FontRegistry::discover(directory) {
+            Ok(registry) => {
+                registry.warn();
+                registry.faces
+            }
+            Err(error) => {
+                eprintln!("Couldn't scan {} for fonts: {}", directory, error);
+                Vec::new()
+            }
+        };
+        FontMap::new(faces)
+    }
+
+    pub fn new(faces: Vec<FontFace>) -> Self {
+        FontMap {
+            fonts: HashMap::new(),
+            faces,
+            fallback_family: fonts::FIRA_SANS,
+        }
+    }
+
+    /// The closest face to `weight` and `style` in `family`, or in the fallback family if
+    /// `family` has no faces
+    ///
+    /// Italic falls back to normal, and weights are matched like CSS does: lighter weights
+    /// than 400 look lighter first, heavier weights than 500 look heavier first, and 400 and
+    /// 500 try each other first.
+    pub fn resolve(&self, family: &str, weight: Weight, style: FontStyle) -> Option<&FontFace> {
+        let in_family = |family: &str| {
+            self.faces
+                .iter()
+                .filter(|face| face.family.eq_ignore_ascii_case(family))
+                .collect::<Vec<_>>()
+        };
+        let mut faces = in_family(family);
+        if faces.is_empty() {
+            faces = in_family(self.fallback_family);
+        }
+        let styled = faces
+            .iter()
+            .filter(|face| face.style == style)
+            .cloned()
+            .collect::<Vec<_>>();
+        let faces = if styled.is_empty() { faces } else { styled };
+        faces
+            .into_iter()
+            .min_by_key(|face| weight_distance(weight, face.weight))
+    }
+
+    /// Loads the face [resolved](FontMap::resolve) for `family`, `weight` and `style`
+    pub fn get_or_load_face<L: LoadFont + ?Sized>(
+        &mut self,
+        loader: &L,
+        family: &str,
+        weight: Weight,
+        style: FontStyle,
+    ) -> Option<Handle<Font>> {
+        let path = self.resolve(family, weight, style)?.path;
+        Some(self.get_or_load(loader, path))
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    pub fn add_face(&mut self, face: FontFace) {
+        self.faces.push(face);
+    }
+
+    /// The handle of the font at `path`, loading it if it isn't loaded, and counting one more
+    /// user of it
+    pub fn get_or_load<L: LoadFont + ?Sized>(&mut self, loader: &L, path: &str) -> Handle<Font> {
+        if let Some(font) = self.fonts.get_mut(path) {
+            font.users += 1;
+            return font.handle.clone();
+        }
+        let handle = loader.load_font(path);
+        self.fonts.insert(
+            path.to_string(),
+            FontUse {
+                handle: handle.clone(),
+                users: 1,
+            },
+        );
+        handle
+    }
+
+    /// [Gets or loads](FontMap::get_or_load) each of `paths`, returning their handles in the
+    /// same order
+    pub fn get_or_load_all<L, I>(&mut self, loader: &L, paths: I) -> Vec<Handle<Font>>
+    where
+        L: LoadFont + ?Sized,
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        paths
+            .into_iter()
+            .map(|path| self.get_or_load(loader, path.as_ref()))
+            .collect()
+    }
+
+    /// [Gets or loads](FontMap::get_or_load) every face of `family`, in the order of
+    /// [faces](FontMap::faces)
+    pub fn get_or_load_family<L: LoadFont + ?Sized>(
         &mut self,
-        asset_server: &Res<AssetServer>,
-        path: &'static str,
-    ) -> Handle<Font> {
-        let font = self.fonts.entry(path).or_insert(asset_server.load(path));
-        font.clone()
+        loader: &L,
+        family: &str,
+    ) -> Vec<Handle<Font>> {
+        let paths = self
+            .faces
+            .iter()
+            .filter(|face| face.family.eq_ignore_ascii_case(family))
+            .map(|face| face.path)
+            .collect::<Vec<_>>();
+        self.get_or_load_all(loader, paths)
+    }
+
+    /// Counts one less user of the font at `path`, dropping the map's handle once it has none
+    ///
+    /// The asset is freed when the last handle to it is dropped, so entities still using the
+    /// font keep it loaded. Returns whether the map no longer holds the font.
+    pub fn unload(&mut self, path: &str) -> bool {
+        let font = match self.fonts.get_mut(path) {
+            Some(font) => font,
+            None => return true,
+        };
+        font.users = font.users.saturating_sub(1);
+        if font.users == 0 {
+            self.fonts.remove(path);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// [Unloads](FontMap::unload) the face [resolved](FontMap::resolve) for `family`, `weight`
+    /// and `style`
+    pub fn unload_face(&mut self, family: &str, weight: Weight, style: FontStyle) -> bool {
+        match self.resolve(family, weight, style) {
+            Some(face) => {
+                let path = face.path;
+                self.unload(path)
+            }
+            None => true,
+        }
     }
-    #[allow(dead_code)]
-    pub fn unload(&mut self, path: &'static str) {
-        self.fonts.remove(path);
+
+    pub fn handles(&self) -> impl Iterator<Item = &Handle<Font>> {
+        self.fonts.values().map(|font| &font.handle)
+    }
+
+    /// The fonts the map holds, sorted by path
+    pub fn resident(&self, fonts: &Assets<Font>) -> ResidentFonts {
+        let mut resident = self
+            .fonts
+            .iter()
+            .map(|(path, font)| ResidentFont {
+                path: path.clone(),
+                users: font.users,
+                loaded: fonts.get(&font.handle).is_some(),
+            })
+            .collect::<Vec<_>>();
+        resident.sort_by(|a, b| a.path.cmp(&b.path));
+        ResidentFonts(resident)
     }
 }
 
+impl LoadFont for AssetServer {
+    fn load_font(&self, path: &str) -> Handle<Font> {
+        self.load(path)
+    }
+}
+
+impl<'a> LoadFont for Res<'a, AssetServer> {
+    fn load_font(&self, path: &str) -> Handle<Font> {
+        self.load(path)
+    }
+}
+
+impl fmt::Display for ResidentFonts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} resident fonts", self.0.len())?;
+        for font in self.0.iter() {
+            writeln!(
+                f,
+                "{:<48} {:>3} users{}",
+                font.path,
+                font.users,
+                if font.loaded { "" } else { ", loading" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Weight {
+    const ALL: [Weight; 10] = [
+        Weight::Thin,
+        Weight::ExtraLight,
+        Weight::Light,
+        Weight::SemiLight,
+        Weight::Regular,
+        Weight::Medium,
+        Weight::SemiBold,
+        Weight::Bold,
+        Weight::ExtraBold,
+        Weight::Black,
+    ];
+
+    /// The weight closest to a numeric weight, e.g. the `usWeightClass` of a font file
+    pub fn nearest(weight: u16) -> Weight {
+        Weight::ALL
+            .iter()
+            .cloned()
+            .min_by_key(|&candidate| (candidate as i32 - weight as i32).abs())
+            .unwrap()
+    }
+}
+
+/// How far `available` is from `wanted`, following the CSS font matching order
+fn weight_distance(wanted: Weight, available: Weight) -> (u8, u16) {
+    let (wanted, available) = (wanted as u16, available as u16);
+    let distance = if available < wanted {
+        wanted - available
+    } else {
+        available - wanted
+    };
+    let rank = if available == wanted {
+        0
+    } else if (400..=500).contains(&wanted) {
+        // up to 500 first, then lighter, then heavier than 500
+        if available > wanted && available <= 500 {
+            1
+        } else if available < wanted {
+            2
+        } else {
+            3
+        }
+    } else if (wanted < 400) == (available < wanted) {
+        // lighter first for light weights, heavier first for heavy ones
+        1
+    } else {
+        2
+    };
+    (rank, distance)
+}
+
 #[allow(dead_code)]
 pub mod fonts {
     pub const FIRASANS_BLACK: &str = "fonts/FiraSans-Black.ttf";
@@ -56,4 +355,186 @@ pub mod fonts {
     pub const JETBRAINSMONO_REGULAR: &str = "fonts/JetBrainsMono-Regular.ttf";
     pub const JETBRAINSMONO_SEMILIGHTITALIC: &str = "fonts/JetBrainsMono-SemiLight-Italic.ttf";
     pub const JETBRAINSMONO_SEMILIGHT: &str = "fonts/JetBrainsMono-SemiLight.ttf";
+
+    /// Scanned for font files by [FontRegistry::discover](super::FontRegistry::discover)
+    pub const DIRECTORY: &str = "fonts";
+
+    pub const FIRA_SANS: &str = "Fira Sans";
+    pub const JETBRAINS_MONO: &str = "JetBrains Mono";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+    use std::cell::RefCell;
+
+    /// Counts the loads of each path, instead of loading anything
+    #[derive(Default)]
+    struct CountingLoader {
+        loads: RefCell<HashMap<String, usize>>,
+    }
+
+    impl CountingLoader {
+        fn loads(&self, path: &str) -> usize {
+            self.loads.borrow().get(path).cloned().unwrap_or(0)
+        }
+
+        fn total(&self) -> usize {
+            self.loads.borrow().values().sum()
+        }
+    }
+
+    impl LoadFont for CountingLoader {
+        fn load_font(&self, path: &str) -> Handle<Font> {
+            *self.loads.borrow_mut().entry(path.to_string()).or_insert(0) += 1;
+            Handle::weak(HandleId::random::<Font>())
+        }
+    }
+
+    fn face(family: &'static str, weight: Weight, path: &'static str) -> FontFace {
+        FontFace {
+            family,
+            weight,
+            style: FontStyle::Normal,
+            path,
+        }
+    }
+
+    fn font_map() -> FontMap {
+        FontMap::new(vec![
+            face("Test Sans", Weight::Regular, "fonts/TestSans-Regular.ttf"),
+            face("Test Sans", Weight::Bold, "fonts/TestSans-Bold.ttf"),
+            face("Test Mono", Weight::Regular, "fonts/TestMono-Regular.ttf"),
+        ])
+    }
+
+    #[test]
+    fn cache_hits_do_not_load() {
+        let loader = CountingLoader::default();
+        let mut font_map = font_map();
+        let first = font_map.get_or_load(&loader, "fonts/TestSans-Regular.ttf");
+        let second = font_map.get_or_load(&loader, "fonts/TestSans-Regular.ttf");
+        assert_eq!(first, second);
+        assert_eq!(loader.loads("fonts/TestSans-Regular.ttf"), 1);
+    }
+
+    #[test]
+    fn batch_loads_each_path_once() {
+        let loader = CountingLoader::default();
+        let mut font_map = font_map();
+        let paths = [
+            "fonts/TestSans-Regular.ttf",
+            "fonts/TestSans-Bold.ttf",
+            "fonts/TestSans-Regular.ttf",
+        ];
+        let handles = font_map.get_or_load_all(&loader, &paths);
+        assert_eq!(handles.len(), 3);
+        assert_eq!(handles[0], handles[2]);
+        assert_ne!(handles[0], handles[1]);
+        font_map.get_or_load_all(&loader, &paths);
+        assert_eq!(loader.loads("fonts/TestSans-Regular.ttf"), 1);
+        assert_eq!(loader.loads("fonts/TestSans-Bold.ttf"), 1);
+        assert_eq!(loader.total(), 2);
+    }
+
+    #[test]
+    fn family_loads_each_face_once() {
+        let loader = CountingLoader::default();
+        let mut font_map = font_map();
+        font_map.get_or_load(&loader, "fonts/TestSans-Bold.ttf");
+        let handles = font_map.get_or_load_family(&loader, "test sans");
+        assert_eq!(handles.len(), 2);
+        assert!(font_map.get_or_load_family(&loader, "Missing").is_empty());
+        assert_eq!(loader.loads("fonts/TestSans-Regular.ttf"), 1);
+        assert_eq!(loader.loads("fonts/TestSans-Bold.ttf"), 1);
+        assert_eq!(loader.loads("fonts/TestMono-Regular.ttf"), 0);
+    }
+
+    #[test]
+    fn loads_again_once_unloaded() {
+        let loader = CountingLoader::default();
+        let mut font_map = font_map();
+        font_map.get_or_load(&loader, "fonts/TestMono-Regular.ttf");
+        font_map.get_or_load(&loader, "fonts/TestMono-Regular.ttf");
+        assert!(!font_map.unload("fonts/TestMono-Regular.ttf"));
+        assert!(font_map.unload("fonts/TestMono-Regular.ttf"));
+        font_map.get_or_load(&loader, "fonts/TestMono-Regular.ttf");
+        assert_eq!(loader.loads("fonts/TestMono-Regular.ttf"), 2);
+    }
+
+    /// Every weight, in the order they're tried for `wanted`
+    fn weight_order(wanted: Weight) -> Vec<Weight> {
+        let mut weights = Weight::ALL.to_vec();
+        weights.sort_by_key(|&weight| weight_distance(wanted, weight));
+        weights
+    }
+
+    #[test]
+    fn regular_tries_medium_then_lighter_then_heavier() {
+        use Weight::*;
+        assert_eq!(
+            weight_order(Regular),
+            vec![
+                Regular, Medium, SemiLight, Light, ExtraLight, Thin, SemiBold, Bold, ExtraBold,
+                Black
+            ]
+        );
+    }
+
+    #[test]
+    fn light_tries_lighter_first() {
+        use Weight::*;
+        assert_eq!(
+            weight_order(Light),
+            vec![
+                Light, ExtraLight, Thin, SemiLight, Regular, Medium, SemiBold, Bold, ExtraBold,
+                Black
+            ]
+        );
+    }
+
+    #[test]
+    fn semi_bold_tries_heavier_first() {
+        use Weight::*;
+        assert_eq!(
+            weight_order(SemiBold),
+            vec![
+                SemiBold, Bold, ExtraBold, Black, Medium, Regular, SemiLight, Light, ExtraLight,
+                Thin
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_missing_weights_and_styles() {
+        let mut font_map = FontMap::new(vec![
+            face("Test Sans", Weight::Light, "fonts/TestSans-Light.ttf"),
+            face("Test Sans", Weight::Medium, "fonts/TestSans-Medium.ttf"),
+            face("Test Sans", Weight::Black, "fonts/TestSans-Black.ttf"),
+        ]);
+        font_map.fallback_family = "Test Sans";
+        let resolve = |family, weight, style| font_map.resolve(family, weight, style).unwrap().path;
+        assert_eq!(
+            resolve("Test Sans", Weight::Regular, FontStyle::Normal),
+            "fonts/TestSans-Medium.ttf"
+        );
+        assert_eq!(
+            resolve("Test Sans", Weight::ExtraLight, FontStyle::Normal),
+            "fonts/TestSans-Light.ttf"
+        );
+        assert_eq!(
+            resolve("Test Sans", Weight::SemiBold, FontStyle::Normal),
+            "fonts/TestSans-Black.ttf"
+        );
+        // no italic faces, so the normal ones are used
+        assert_eq!(
+            resolve("Test Sans", Weight::Regular, FontStyle::Italic),
+            "fonts/TestSans-Medium.ttf"
+        );
+        assert_eq!(
+            resolve("Missing", Weight::Light, FontStyle::Normal),
+            "fonts/TestSans-Light.ttf"
+        );
+    }
 }
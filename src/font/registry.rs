@@ -0,0 +1,308 @@
+use super::{FontFace, FontStyle, Weight};
+use crate::leak;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The faces found by scanning a fonts directory in the assets directory
+#[derive(Debug, Default)]
+pub struct FontRegistry {
+    pub faces: Vec<FontFace>,
+    /// Asset paths of the fonts with no license in the `LICENSES` directory next to them
+    pub unlicensed: Vec<&'static str>,
+    /// Font files that couldn't be read, and why
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+impl FontRegistry {
+    /// Scans `directory`, relative to the assets directory, for `.ttf` and `.otf` files, reading
+    /// the family and subfamily from each one's name table and its weight from its OS/2 table
+    ///
+    /// A font is licensed if `LICENSES` in `directory` has a file named after its family
+    /// without spaces, e.g. `FiraSans.txt` for Fira Sans.
+    pub fn discover(directory: &str) -> io::Result<Self> {
+        let root = assets_root().join(directory);
+        let licenses = license_names(&root.join("LICENSES"));
+        let mut paths = fs::read_dir(&root)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_font_file(path))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut registry = FontRegistry::default();
+        // Leaked once per family and file, as faces are static
+        let mut families = HashMap::new();
+        for path in paths {
+            let parsed = match fs::read(&path).and_then(|data| parse_face(&data)) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    registry.unreadable.push((path, error));
+                    continue;
+                }
+            };
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            let asset_path = leak(format!("{}/{}", directory, file_name));
+            let family = *families
+                .entry(parsed.family.clone())
+                .or_insert_with(|| leak(parsed.family.clone()));
+            if !licenses.contains(&compact_name(family)) {
+                registry.unlicensed.push(asset_path);
+            }
+            registry.faces.push(FontFace {
+                family,
+                weight: parsed.weight,
+                style: parsed.style,
+                path: asset_path,
+            });
+        }
+        Ok(registry)
+    }
+
+    /// Prints the fonts that couldn't be read or have no license
+    pub fn warn(&self) {
+        for (path, error) in self.unreadable.iter() {
+            eprintln!("Couldn't read font {}: {}", path.display(), error);
+        }
+        for path in self.unlicensed.iter() {
+            eprintln!("Font {} has no license in LICENSES", path);
+        }
+    }
+}
+
+/// The family, weight and style read from a font file
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedFace {
+    family: String,
+    weight: Weight,
+    style: FontStyle,
+}
+
+/// The assets directory, found the same way the [AssetServer](bevy::asset::AssetServer) finds it
+fn assets_root() -> PathBuf {
+    let root = match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => PathBuf::from(manifest_dir),
+        Err(_) => env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .unwrap_or_default(),
+    };
+    root.join("assets")
+}
+
+fn is_font_file(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("ttf") || extension.eq_ignore_ascii_case("otf")
+        }
+        None => false,
+    }
+}
+
+/// The license file stems in `directory`, compared as [compact_name]s
+fn license_names(directory: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            path.file_stem()
+                .map(|stem| compact_name(&stem.to_string_lossy()))
+        })
+        .collect()
+}
+
+/// `name` lowercased without spaces, so `Fira Sans` matches `FiraSans.txt`
+fn compact_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn malformed(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> io::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| malformed("font file is truncated"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    Ok((read_u16(data, offset)? as u32) << 16 | read_u16(data, offset + 2)? as u32)
+}
+
+/// The bytes of the table tagged `tag`, from the table directory of an sfnt font
+fn find_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> io::Result<&'a [u8]> {
+    let tables = read_u16(data, 4)? as usize;
+    for table in 0..tables {
+        let record = 12 + table * 16;
+        if data.get(record..record + 4) == Some(&tag[..]) {
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            return data
+                .get(offset..offset + length)
+                .ok_or_else(|| malformed("font table is truncated"));
+        }
+    }
+    Err(malformed(&format!(
+        "font has no {} table",
+        String::from_utf8_lossy(tag)
+    )))
+}
+
+/// The English name with `name_id` from a name table, preferring Windows names
+fn find_name(table: &[u8], name_id: u16) -> io::Result<Option<String>> {
+    let count = read_u16(table, 2)? as usize;
+    let strings = read_u16(table, 4)? as usize;
+    let mut best: Option<(u8, String)> = None;
+    for record in 0..count {
+        let record = 6 + record * 12;
+        if read_u16(table, record + 6)? != name_id {
+            continue;
+        }
+        let platform = read_u16(table, record)?;
+        let encoding = read_u16(table, record + 2)?;
+        let language = read_u16(table, record + 4)?;
+        let length = read_u16(table, record + 8)? as usize;
+        let offset = strings + read_u16(table, record + 10)? as usize;
+        let bytes = table
+            .get(offset..offset + length)
+            .ok_or_else(|| malformed("font name is truncated"))?;
+        let (rank, name) = match (platform, encoding, language) {
+            (3, 1, 0x409) | (3, 10, 0x409) => (0, utf16_be(bytes)),
+            (0, _, _) => (1, utf16_be(bytes)),
+            (1, 0, 0) => (2, bytes.iter().map(|&byte| byte as char).collect()),
+            _ => continue,
+        };
+        if best.as_ref().map_or(true, |(best, _)| rank < *best) {
+            best = Some((rank, name));
+        }
+    }
+    Ok(best.map(|(_, name)| name))
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// The weight named in a subfamily like `ExtraLight Italic`
+fn weight_from_name(subfamily: &str) -> Option<Weight> {
+    let name = compact_name(&subfamily.replace('-', ""));
+    // Compound names first, so `extralight` isn't read as `light`
+    let names = [
+        ("extralight", Weight::ExtraLight),
+        ("ultralight", Weight::ExtraLight),
+        ("semilight", Weight::SemiLight),
+        ("extrabold", Weight::ExtraBold),
+        ("ultrabold", Weight::ExtraBold),
+        ("semibold", Weight::SemiBold),
+        ("demibold", Weight::SemiBold),
+        ("thin", Weight::Thin),
+        ("hairline", Weight::Thin),
+        ("light", Weight::Light),
+        ("regular", Weight::Regular),
+        ("medium", Weight::Medium),
+        ("bold", Weight::Bold),
+        ("black", Weight::Black),
+        ("heavy", Weight::Black),
+    ];
+    names
+        .iter()
+        .find(|(weight_name, _)| name.contains(weight_name))
+        .map(|&(_, weight)| weight)
+}
+
+/// Reads the family, weight and style of a TrueType or OpenType font
+fn parse_face(data: &[u8]) -> io::Result<ParsedFace> {
+    let names = find_table(data, b"name")?;
+    // The typographic names group every weight into one family, where the legacy names only
+    // group regular, bold and their italics
+    let family = match find_name(names, 16)? {
+        Some(family) => family,
+        None => find_name(names, 1)?.ok_or_else(|| malformed("font has no family name"))?,
+    };
+    let subfamily = match find_name(names, 17)? {
+        Some(subfamily) => subfamily,
+        None => find_name(names, 2)?.unwrap_or_default(),
+    };
+
+    let (class, italic) = match find_table(data, b"OS/2") {
+        // usWeightClass, and fsSelection's ITALIC or OBLIQUE bits
+        Ok(os2) => (
+            Some(read_u16(os2, 4)?),
+            read_u16(os2, 62)? & (1 | 1 << 9) != 0,
+        ),
+        Err(_) => (None, false),
+    };
+    // Some families shift their light weights' classes up, e.g. Fira Sans Thin is 250, so the
+    // subfamily's weight is trusted over the class
+    let weight = weight_from_name(&subfamily)
+        .or_else(|| class.map(Weight::nearest))
+        .unwrap_or(Weight::Regular);
+    let italic = italic || subfamily.to_lowercase().contains("italic");
+    Ok(ParsedFace {
+        family,
+        weight,
+        style: if italic {
+            FontStyle::Italic
+        } else {
+            FontStyle::Normal
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::fonts;
+
+    #[test]
+    fn discovers_bundled_fonts() {
+        let registry = FontRegistry::discover(fonts::DIRECTORY).unwrap();
+        assert_eq!(registry.faces.len(), 32);
+        let mut families = registry
+            .faces
+            .iter()
+            .map(|face| face.family)
+            .collect::<Vec<_>>();
+        families.sort();
+        families.dedup();
+        assert_eq!(families, vec![fonts::FIRA_SANS, fonts::JETBRAINS_MONO]);
+        let face = |path| {
+            registry
+                .faces
+                .iter()
+                .find(|face| face.path == path)
+                .unwrap()
+        };
+        // usWeightClass 250, but named Thin
+        assert_eq!(face(fonts::FIRASANS_THIN).weight, Weight::Thin);
+        assert_eq!(
+            face(fonts::JETBRAINSMONO_SEMILIGHT).weight,
+            Weight::SemiLight
+        );
+        assert!(registry.unlicensed.is_empty());
+        assert!(registry.unreadable.is_empty());
+    }
+
+    #[test]
+    fn truncated_font_is_an_error() {
+        let data = fs::read(assets_root().join(fonts::FIRASANS_THIN)).unwrap();
+        assert!(parse_face(&data).is_ok());
+        for length in [0, 3, 12, 64, 256, 1024].iter() {
+            assert!(parse_face(&data[..*length]).is_err());
+        }
+    }
+}
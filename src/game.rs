@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameState {
+    /// Waits for the preloaded assets, showing their progress
+    Loading,
+    Starting,
+    Restarting,
+    /// Encloses the levels, and the pause overlay
+    Playing,
+    Level(u32),
+    Paused,
+    Win,
+    Lose,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    Loaded,
+    StartPause,
+    Restart,
+    Restarted,
+    LevelComplete,
+    Won,
+    Lost,
+}
+
+impl GameState {
+    pub fn next(&self, event: &GameEvent) -> Option<GameState> {
+        match (self, event) {
+            (GameState::Loading, GameEvent::Loaded) => Some(GameState::Starting),
+            (GameState::Loading, _) => None,
+            (GameState::Starting, GameEvent::StartPause) => Some(GameState::Level(1)),
+            (GameState::Level(_), GameEvent::StartPause) => Some(GameState::Paused),
+            // restores the level the pause overlay covers
+            (GameState::Paused, GameEvent::StartPause) => Some(GameState::Playing),
+            (GameState::Win, GameEvent::StartPause) => Some(GameState::Restarting),
+            (GameState::Lose, GameEvent::StartPause) => Some(GameState::Restarting),
+            (GameState::Level(n), GameEvent::LevelComplete) => Some(GameState::Level(n + 1)),
+            (GameState::Level(_), GameEvent::Won) => Some(GameState::Win),
+            (GameState::Level(_), GameEvent::Lost) => Some(GameState::Lose),
+            (GameState::Restarting, GameEvent::Restarted) => Some(GameState::Starting),
+            (GameState::Restarting, GameEvent::Restart) => None,
+            (_, GameEvent::Restart) => Some(GameState::Restarting),
+            _ => None,
+        }
+    }
+
+    pub fn parent(&self) -> Option<GameState> {
+        match self {
+            GameState::Level(_) | GameState::Paused => Some(GameState::Playing),
+            _ => None,
+        }
+    }
+
+    pub fn is_overlay(&self) -> bool {
+        *self == GameState::Paused
+    }
+}
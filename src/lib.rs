@@ -1,2 +1,11 @@
+pub mod behaviour_tree;
 pub mod display_diagnostic;
 pub mod font;
+pub mod game;
+pub mod loading;
+pub mod state_machine;
+
+/// Leaks `value` for the rest of the run, for names made once at startup but needed as `'static`
+pub(crate) fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
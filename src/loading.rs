@@ -0,0 +1,169 @@
+use crate::font::FontMap;
+
+use bevy::asset::{HandleUntyped, LoadState};
+use bevy::prelude::*;
+
+/// An App Plugin that starts loading `fonts` at startup, and tracks every asset added to the
+/// [LoadProgress] resource
+#[derive(Default)]
+pub struct LoadingPlugin {
+    /// Font paths kept loaded for the whole run, so text using them shows straight away
+    pub fonts: Vec<String>,
+}
+
+/// How far the tracked assets have got with loading, e.g. to hold a loading screen until
+/// they're all [done](LoadProgress::is_done)
+#[derive(Default)]
+pub struct LoadProgress {
+    assets: Vec<TrackedAsset>,
+}
+
+struct TrackedAsset {
+    path: String,
+    handle: HandleUntyped,
+    state: LoadState,
+}
+
+impl LoadingPlugin {
+    pub fn preload_font(mut self, path: &str) -> Self {
+        self.fonts.push(path.to_string());
+        self
+    }
+}
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let fonts = self.fonts.clone();
+        app.init_resource::<LoadProgress>()
+            .add_startup_system(
+                (move |asset_server: Res<AssetServer>,
+                       mut font_map: ResMut<FontMap>,
+                       mut progress: ResMut<LoadProgress>| {
+                    let handles = font_map.get_or_load_all(&asset_server, &fonts);
+                    for (path, handle) in fonts.iter().zip(handles) {
+                        progress.track(path, handle.clone_untyped());
+                    }
+                })
+                .system(),
+            )
+            .add_system_to_stage(stage::PRE_UPDATE, update_load_progress_system.system());
+    }
+}
+
+impl LoadProgress {
+    /// Tracks the loading of the asset at `path`, unless it's already tracked
+    pub fn track(&mut self, path: &str, handle: HandleUntyped) {
+        if self.assets.iter().all(|asset| asset.path != path) {
+            self.assets.push(TrackedAsset {
+                path: path.to_string(),
+                handle,
+                state: LoadState::NotLoaded,
+            });
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn loaded(&self) -> usize {
+        self.count(LoadState::Loaded)
+    }
+
+    /// The paths of the assets that couldn't be loaded
+    pub fn failed(&self) -> impl Iterator<Item = &str> {
+        self.assets
+            .iter()
+            .filter(|asset| asset.state == LoadState::Failed)
+            .map(|asset| asset.path.as_str())
+    }
+
+    /// The fraction of assets that have loaded or failed, 1 when none are tracked
+    pub fn progress(&self) -> f32 {
+        if self.assets.is_empty() {
+            1.0
+        } else {
+            (self.loaded() + self.count(LoadState::Failed)) as f32 / self.total() as f32
+        }
+    }
+
+    /// Whether every asset has loaded or failed
+    pub fn is_done(&self) -> bool {
+        self.assets
+            .iter()
+            .all(|asset| asset.state == LoadState::Loaded || asset.state == LoadState::Failed)
+    }
+
+    fn count(&self, state: LoadState) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| asset.state == state)
+            .count()
+    }
+}
+
+/// Updates the load state of every tracked asset, warning when one fails
+fn update_load_progress_system(asset_server: Res<AssetServer>, mut progress: ResMut<LoadProgress>) {
+    for asset in progress.assets.iter_mut() {
+        let state = asset_server.get_load_state(&asset.handle);
+        if state == LoadState::Failed && asset.state != LoadState::Failed {
+            eprintln!("Failed to load {}", asset.path);
+        }
+        asset.state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+
+    fn handle() -> HandleUntyped {
+        Handle::<Font>::weak(HandleId::random::<Font>()).clone_untyped()
+    }
+
+    fn set_state(progress: &mut LoadProgress, path: &str, state: LoadState) {
+        for asset in progress.assets.iter_mut() {
+            if asset.path == path {
+                asset.state = state;
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_tracked_is_done() {
+        let progress = LoadProgress::default();
+        assert_eq!(progress.progress(), 1.0);
+        assert!(progress.is_done());
+    }
+
+    #[test]
+    fn tracking_a_path_twice_counts_it_once() {
+        let mut progress = LoadProgress::default();
+        progress.track("fonts/a.ttf", handle());
+        progress.track("fonts/a.ttf", handle());
+        progress.track("fonts/b.ttf", handle());
+        assert_eq!(progress.total(), 2);
+    }
+
+    #[test]
+    fn failures_count_as_done() {
+        let mut progress = LoadProgress::default();
+        for path in &["fonts/a.ttf", "fonts/b.ttf", "fonts/c.ttf", "fonts/d.ttf"] {
+            progress.track(path, handle());
+        }
+        assert_eq!(progress.progress(), 0.0);
+        assert!(!progress.is_done());
+        set_state(&mut progress, "fonts/a.ttf", LoadState::Loaded);
+        set_state(&mut progress, "fonts/b.ttf", LoadState::Failed);
+        set_state(&mut progress, "fonts/c.ttf", LoadState::Loading);
+        assert_eq!(progress.progress(), 0.5);
+        assert_eq!(progress.loaded(), 1);
+        assert_eq!(progress.failed().collect::<Vec<_>>(), vec!["fonts/b.ttf"]);
+        assert!(!progress.is_done());
+        set_state(&mut progress, "fonts/c.ttf", LoadState::Failed);
+        set_state(&mut progress, "fonts/d.ttf", LoadState::Loaded);
+        assert_eq!(progress.progress(), 1.0);
+        assert!(progress.is_done());
+    }
+}
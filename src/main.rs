@@ -2,38 +2,143 @@
 #![doc(include = "../README.md")]
 
 use bevy::{
-    app::AppExit, diagnostic::FrameTimeDiagnosticsPlugin, diagnostic::PrintDiagnosticsPlugin,
-    prelude::*,
+    app::AppExit, diagnostic::DiagnosticId, diagnostic::FrameTimeDiagnosticsPlugin,
+    diagnostic::PrintDiagnosticsPlugin, prelude::*,
 };
 
-mod display_diagnostic;
-use display_diagnostic::DisplayDiagnosticsPlugin;
-
-mod font;
-use font::{fonts, FontMap};
+use bevy_2d_template::display_diagnostic::{
+    ComponentCountDiagnosticsPlugin, DiagnosticsGraphStyle, DisplayDiagnosticsPlugin,
+    DisplayDiagnosticsStyle, EntityCountDiagnosticsPlugin, FontDiagnosticsPlugin, RecordSettings,
+    StageTimeDiagnosticsPlugin, Threshold,
+};
+use bevy_2d_template::font::{fonts, FontMap, FontStyle, Weight};
+use bevy_2d_template::game::{GameEvent, GameState};
+use bevy_2d_template::loading::{LoadProgress, LoadingPlugin};
+use bevy_2d_template::state_machine::{
+    DespawnOnExit, StateHistory, StateMachinePlugin, StateSystemsAppExt,
+};
 
 fn main() {
     App::build()
         .add_default_plugins()
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(DisplayDiagnosticsPlugin::default())
-        .add_resource(GameState::Starting)
+        .add_plugin(EntityCountDiagnosticsPlugin)
+        .add_plugin(StageTimeDiagnosticsPlugin::default())
+        .add_plugin(FontDiagnosticsPlugin)
+        .add_plugin(ComponentCountDiagnosticsPlugin::<DespawnOnEnd>::new(
+            DespawnOnEnd::COUNT,
+            "despawn_on_end",
+        ))
+        .add_plugin(
+            DisplayDiagnosticsPlugin::default()
+                .with_style(DisplayDiagnosticsStyle {
+                    graph: Some(DiagnosticsGraphStyle::default()),
+                    ..Default::default()
+                })
+                .persist_to("diagnostics.ron")
+                .with_threshold(Threshold::above(
+                    FrameTimeDiagnosticsPlugin::FRAME_TIME,
+                    1.0 / 60.0,
+                    1.0 / 30.0,
+                ))
+                .recording(RecordSettings {
+                    toggle_key: Some(KeyCode::F4),
+                    ..Default::default()
+                }),
+        )
+        .add_plugin(LoadingPlugin::default().preload_font(fonts::JETBRAINSMONO_MEDIUM))
+        .add_plugin(
+            StateMachinePlugin::new(GameState::Loading, GameState::next)
+                .with_hierarchy(GameState::parent, GameState::is_overlay),
+        )
+        // an orthogonal region, running alongside the game state
+        .add_plugin(StateMachinePlugin::new(MusicState::On, MusicState::next))
+        .on_enter(GameState::Loading, spawn_loading_screen_system.system())
+        .on_update(GameState::Loading, loading_screen_system.system())
+        .on_exit(GameState::Loading, spawn_text_system.system())
+        .on_enter(GameState::Starting, start_game_system.system())
+        .on_enter(GameState::Restarting, end_game_system.system())
+        .on_enter(GameState::Paused, pause_game_system.system())
+        .on_exit(GameState::Paused, unpause_game_system.system())
         .add_resource(ClearColor(Color::BLACK)) // the window's background colour
-        .init_resource::<FontMap>()
+        .add_resource(FontMap::discover(fonts::DIRECTORY))
         .add_startup_system(setup.system())
-        .add_startup_system(start_game_system.system())
         .add_system(test_change_text_system_a.system())
         .add_system(test_change_text_system_b.system())
-        .add_system(end_game_system.system())
         .add_system(start_pause_game_system.system())
+        .add_system(toggle_music_system.system())
+        .add_system(print_game_history_system.system())
+        .add_system(print_fonts_system.system())
         .add_system(exit_on_esc_system.system())
         .run();
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut font_map: ResMut<FontMap>) {
+fn setup(mut commands: Commands) {
     commands
         .spawn(Camera2dComponents::default())
-        .spawn(UiCameraComponents::default())
+        .spawn(UiCameraComponents::default());
+}
+
+struct LoadingBar;
+
+fn spawn_loading_screen_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn(NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Px(10.0)),
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
+            ..Default::default()
+        })
+        .with(DespawnOnExit(GameState::Loading))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeComponents {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::WHITE.into()),
+                    ..Default::default()
+                })
+                .with(LoadingBar);
+        });
+}
+
+fn loading_screen_system(
+    progress: Res<LoadProgress>,
+    mut game_events: ResMut<Events<GameEvent>>,
+    mut bar_query: Query<(&mut Style, &LoadingBar)>,
+) {
+    for (mut style, _) in &mut bar_query.iter() {
+        style.size.width = Val::Percent(progress.progress() * 100.0);
+    }
+    if progress.is_done() {
+        // text using a font that failed just won't show
+        let failed = progress.failed().count();
+        if failed > 0 {
+            eprintln!(
+                "Starting with {} of {} assets missing",
+                failed,
+                progress.total()
+            );
+        }
+        game_events.send(GameEvent::Loaded);
+    }
+}
+
+fn spawn_text_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut font_map: ResMut<FontMap>,
+) {
+    let font = font_map.get_or_load(&asset_server, fonts::JETBRAINSMONO_MEDIUM);
+    commands
         // .spawn(TextComponents {
         //     text: Text {
         //         font: asset_server.load(fonts::JETBRAINSMONO_MEDIUM),
@@ -56,7 +161,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut font_map: R
         // })
         .spawn(TextComponents {
             text: Text {
-                font: asset_server.load(fonts::JETBRAINSMONO_MEDIUM),
+                font: font.clone(),
                 value: "Thistextwraps".to_string(),
                 style: TextStyle {
                     color: Color::RED * [1.0, 1.0, 1.0, 0.5],
@@ -76,7 +181,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, mut font_map: R
         })
         .spawn(TextComponents {
             text: Text {
-                font: asset_server.load(fonts::JETBRAINSMONO_MEDIUM),
+                font,
                 value: "This\ntext\nwraps".to_string(),
                 style: TextStyle {
                     color: Color::WHITE * [1.0, 1.0, 1.0, 0.5],
@@ -135,18 +240,13 @@ fn test_change_text_system_b(mut text_query: Query<(&mut Text, &B)>) {
 
 fn end_game_system(
     mut commands: Commands,
-    mut game_state: ResMut<GameState>,
-    materials: ResMut<Assets<ColorMaterial>>,
+    mut game_events: ResMut<Events<GameEvent>>,
     mut despawn_query: Query<(Entity, &DespawnOnEnd)>,
-    // color_material_handle_query: Query<&Handle<ColorMaterial>>,
 ) {
-    if *game_state == GameState::Restarting {
-        for (entity, _) in &mut despawn_query.iter() {
-            commands.despawn(entity);
-        }
-        start_game_system(commands, materials);
-        *game_state = GameState::Starting;
+    for (entity, _) in &mut despawn_query.iter() {
+        commands.despawn(entity);
     }
+    game_events.send(GameEvent::Restarted);
 }
 
 fn start_game_system(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
@@ -164,32 +264,113 @@ fn start_game_system(mut commands: Commands, mut materials: ResMut<Assets<ColorM
         .with(DespawnOnEnd);
 }
 
-fn start_pause_game_system(mut game_state: ResMut<GameState>, keyboard_input: Res<Input<KeyCode>>) {
+fn pause_game_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut font_map: ResMut<FontMap>,
+) {
+    commands
+        .spawn(TextComponents {
+            text: Text {
+                font: font_map
+                    .get_or_load_face(
+                        &asset_server,
+                        fonts::JETBRAINS_MONO,
+                        Weight::Medium,
+                        FontStyle::Normal,
+                    )
+                    .unwrap_or_default(),
+                value: "Paused".to_string(),
+                style: TextStyle {
+                    color: Color::WHITE,
+                    font_size: 40.0,
+                },
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(DespawnOnExit(GameState::Paused));
+}
+
+fn unpause_game_system(mut font_map: ResMut<FontMap>) {
+    // the pause text is despawned on exit too
+    font_map.unload_face(fonts::JETBRAINS_MONO, Weight::Medium, FontStyle::Normal);
+}
+
+fn start_pause_game_system(
+    mut game_events: ResMut<Events<GameEvent>>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
     if keyboard_input.just_released(KeyCode::Space) {
-        *game_state = match *game_state {
-            GameState::Starting => GameState::Playing,
-            GameState::Restarting => GameState::Restarting,
-            GameState::Playing => GameState::Paused,
-            GameState::Paused => GameState::Playing,
-            GameState::Win => GameState::Restarting,
-            GameState::Lose => GameState::Restarting,
-        }
+        game_events.send(GameEvent::StartPause);
     } else if keyboard_input.just_released(KeyCode::R) {
-        *game_state = GameState::Restarting;
+        game_events.send(GameEvent::Restart);
+    }
+}
+
+fn toggle_music_system(
+    mut music_events: ResMut<Events<MusicEvent>>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if keyboard_input.just_released(KeyCode::M) {
+        music_events.send(MusicEvent::Toggle);
+    }
+}
+
+fn print_game_history_system(
+    history: Res<StateHistory<GameState, GameEvent>>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if keyboard_input.just_released(KeyCode::H) {
+        match history.to_ron() {
+            Ok(ron) => println!("{}", ron),
+            Err(error) => println!("Could not serialise game history: {}", error),
+        }
+    }
+}
+
+fn print_fonts_system(
+    font_map: Res<FontMap>,
+    fonts: Res<Assets<Font>>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if keyboard_input.just_released(KeyCode::F) {
+        print!("{}", font_map.resident(&fonts));
     }
 }
 
 struct DespawnOnEnd;
 
-#[derive(PartialEq, Eq)]
-#[allow(dead_code)]
-enum GameState {
-    Starting,
-    Restarting,
-    Playing,
-    Paused,
-    Win,
-    Lose,
+impl DespawnOnEnd {
+    const COUNT: DiagnosticId = DiagnosticId::from_u128(0xa0fa89daeeff4a95a8020ecd495bfdee);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MusicState {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MusicEvent {
+    Toggle,
+}
+
+impl MusicState {
+    fn next(&self, event: &MusicEvent) -> Option<MusicState> {
+        match (self, event) {
+            (MusicState::On, MusicEvent::Toggle) => Some(MusicState::Off),
+            (MusicState::Off, MusicEvent::Toggle) => Some(MusicState::On),
+        }
+    }
 }
 
 fn exit_on_esc_system(
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+mod asset;
+pub use asset::{
+    AssetStateEvent, AssetStateMachine, AssetStateMachinePlugin, StateMachineAsset,
+    StateMachineAssetError, StateMachineAssetLoader, StateMachineCallbacks,
+};
+
+mod graph;
+pub use graph::StateGraph;
+
+mod hierarchy;
+pub use hierarchy::Hierarchy;
+
+mod history;
+pub use history::{records_from_ron, replay, ReplayDivergence, StateHistory, TransitionRecord};
+
+mod despawn;
+pub use despawn::{despawn_on_exit_system, DespawnOnExit};
+
+mod state_systems;
+pub use state_systems::{StateSystems, StateSystemsAppExt};
+
+#[macro_use]
+mod typestate;
+
+mod table;
+pub use table::{TransitionError, TransitionFn, TransitionTable};
+pub use typestate::Transition;
+
+/// A type that can be used as the state of a [StateMachine]
+pub trait MachineState: Clone + Debug + PartialEq + Send + Sync + 'static {}
+
+impl<T: Clone + Debug + PartialEq + Send + Sync + 'static> MachineState for T {}
+
+/// A type that can be used as an event driving a [StateMachine]
+pub trait MachineEvent: Clone + Debug + Send + Sync + 'static {}
+
+impl<T: Clone + Debug + Send + Sync + 'static> MachineEvent for T {}
+
+/// An App Plugin that drives a [StateMachine] of `S` states from a stream of `E` events
+pub struct StateMachinePlugin<S, E> {
+    pub initial: S,
+    pub transition: TransitionFn<S, E>,
+    /// How many handled events to keep in the [StateHistory]
+    pub history: usize,
+    pub hierarchy: Hierarchy<S>,
+    _marker: PhantomData<fn(E)>,
+}
+
+/// The current state of a [StateMachinePlugin], stored as a resource
+#[derive(Debug)]
+pub struct StateMachine<S> {
+    current: S,
+    previous: Option<S>,
+    overlaid: Vec<S>,
+    hierarchy: Hierarchy<S>,
+}
+
+/// The transition function used by the [StateMachinePlugin]
+struct Transitions<S, E>(TransitionFn<S, E>);
+
+/// Sent when the [StateMachine] moves from one state to another
+#[derive(Debug, Clone)]
+pub struct StateChanged<S> {
+    pub from: S,
+    pub to: S,
+    /// The states left by this transition, outermost first
+    pub exited: Vec<S>,
+    /// The states entered by this transition, outermost first
+    pub entered: Vec<S>,
+}
+
+impl<S: MachineState, E: MachineEvent> StateMachinePlugin<S, E> {
+    pub fn new(initial: S, transition: fn(&S, &E) -> Option<S>) -> Self {
+        StateMachinePlugin {
+            initial,
+            transition: TransitionFn::Function(transition),
+            history: 64,
+            hierarchy: Hierarchy::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Drives the state machine from a [TransitionTable], whose guards can reject events
+    pub fn with_table(initial: S, table: TransitionTable<S, E>) -> Self {
+        StateMachinePlugin {
+            transition: TransitionFn::Table(Arc::new(table)),
+            ..Self::new(initial, |_, _| None)
+        }
+    }
+
+    /// Nests states inside the states returned by `parent`, and makes the states matching
+    /// `is_overlay` overlays (see [Hierarchy])
+    pub fn with_hierarchy(
+        mut self,
+        parent: fn(&S) -> Option<S>,
+        is_overlay: fn(&S) -> bool,
+    ) -> Self {
+        self.hierarchy = Hierarchy { parent, is_overlay };
+        self
+    }
+
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = capacity;
+        self
+    }
+
+    /// The stage the transitions of this state machine are applied in
+    pub fn stage() -> &'static str {
+        std::any::type_name::<StateMachine<S>>()
+    }
+
+    fn transition_system(
+        transitions: Res<Transitions<S, E>>,
+        mut machine: ResMut<StateMachine<S>>,
+        mut reader: Local<EventReader<E>>,
+        events: Res<Events<E>>,
+        mut changed_events: ResMut<Events<StateChanged<S>>>,
+        mut rejected_events: ResMut<Events<TransitionError<S, E>>>,
+        mut history: ResMut<StateHistory<S, E>>,
+    ) {
+        history.next_frame();
+        for event in reader.iter(&events) {
+            match transitions.0.next(&machine.current, event) {
+                Ok(next) => {
+                    let changed = machine.apply(next);
+                    history.push(
+                        changed.from.clone(),
+                        event.clone(),
+                        Some(changed.to.clone()),
+                    );
+                    changed_events.send(changed);
+                }
+                Err(error) => {
+                    history.push(machine.current.clone(), event.clone(), None);
+                    rejected_events.send(error);
+                }
+            }
+        }
+    }
+}
+
+impl<S: MachineState, E: MachineEvent> Plugin for StateMachinePlugin<S, E> {
+    fn build(&self, app: &mut AppBuilder) {
+        let machine = StateMachine::new(self.initial.clone()).with_hierarchy(self.hierarchy);
+        app.add_event::<E>()
+            .add_event::<StateChanged<S>>()
+            .add_event::<TransitionError<S, E>>()
+            .add_resource(StateSystems::new(machine.active()))
+            .add_resource(machine)
+            .add_resource(Transitions::<S, E>(self.transition.clone()))
+            .add_resource(StateHistory::<S, E>::new(self.history))
+            .add_stage_after(stage::UPDATE, Self::stage())
+            .add_system_to_stage(Self::stage(), Self::transition_system.system())
+            .add_system_to_stage(
+                Self::stage(),
+                StateSystems::<S>::run_state_systems.thread_local_system(),
+            )
+            .add_system_to_stage(Self::stage(), despawn_on_exit_system::<S>.system());
+    }
+}
+
+impl<S: MachineState> StateMachine<S> {
+    pub fn new(initial: S) -> Self {
+        StateMachine {
+            current: initial,
+            previous: None,
+            overlaid: Vec::new(),
+            hierarchy: Hierarchy::default(),
+        }
+    }
+
+    pub fn with_hierarchy(mut self, hierarchy: Hierarchy<S>) -> Self {
+        self.hierarchy = hierarchy;
+        self
+    }
+
+    pub fn current(&self) -> &S {
+        &self.current
+    }
+
+    pub fn previous(&self) -> Option<&S> {
+        self.previous.as_ref()
+    }
+
+    pub fn is(&self, state: &S) -> bool {
+        self.current == *state
+    }
+}
@@ -0,0 +1,507 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::type_registry::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A state machine described in a RON `.fsm` asset
+///
+/// ```ron
+/// (
+///     initial: "Idle",
+///     states: [
+///         (name: "Idle"),
+///         (name: "Chasing", on_enter: ["alert"]),
+///         (name: "Dead", terminal: true),
+///     ],
+///     transitions: [
+///         (from: "Idle", event: "PlayerSeen", to: "Chasing"),
+///         (from: "Chasing", event: "Hit", to: "Dead", guard: Some("out_of_health")),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "1c1491b8-9deb-4dc0-83aa-c66475e83278"]
+pub struct StateMachineAsset {
+    pub initial: String,
+    pub states: Vec<StateDef>,
+    pub transitions: Vec<TransitionDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StateDef {
+    pub name: String,
+    /// Actions run when the state is entered
+    #[serde(default)]
+    pub on_enter: Vec<String>,
+    /// Actions run when the state is left
+    #[serde(default)]
+    pub on_exit: Vec<String>,
+    /// Whether the state is meant to have no transitions out of it
+    #[serde(default)]
+    pub terminal: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransitionDef {
+    pub from: String,
+    pub event: String,
+    pub to: String,
+    /// Names a guard that must pass for the transition to be taken
+    #[serde(default)]
+    pub guard: Option<String>,
+    /// Actions run between leaving `from` and entering `to`
+    #[serde(default)]
+    pub actions: Vec<String>,
+}
+
+/// Why a [StateMachineAsset] was rejected at load time
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateMachineAssetError {
+    UnknownState(String),
+    DuplicateState(String),
+    DuplicateTransition {
+        from: String,
+        event: String,
+    },
+    /// A state with no transitions out of it that isn't marked `terminal`
+    DeadEnd(String),
+}
+
+impl fmt::Display for StateMachineAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateMachineAssetError::UnknownState(state) => write!(f, "unknown state {}", state),
+            StateMachineAssetError::DuplicateState(state) => {
+                write!(f, "state {} is declared more than once", state)
+            }
+            StateMachineAssetError::DuplicateTransition { from, event } => write!(
+                f,
+                "more than one transition from {} on {} with the same guard",
+                from, event
+            ),
+            StateMachineAssetError::DeadEnd(state) => write!(
+                f,
+                "state {} has no transitions out of it and isn't marked terminal",
+                state
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateMachineAssetError {}
+
+impl StateMachineAsset {
+    pub fn state(&self, name: &str) -> Option<&StateDef> {
+        self.states.iter().find(|state| state.name == name)
+    }
+
+    pub fn validate(&self) -> Result<(), StateMachineAssetError> {
+        for (i, state) in self.states.iter().enumerate() {
+            if self.states[..i].iter().any(|s| s.name == state.name) {
+                return Err(StateMachineAssetError::DuplicateState(state.name.clone()));
+            }
+        }
+        if self.state(&self.initial).is_none() {
+            return Err(StateMachineAssetError::UnknownState(self.initial.clone()));
+        }
+        for (i, transition) in self.transitions.iter().enumerate() {
+            for state in [&transition.from, &transition.to].iter() {
+                if self.state(state).is_none() {
+                    return Err(StateMachineAssetError::UnknownState((*state).clone()));
+                }
+            }
+            if self.transitions[..i].iter().any(|t| {
+                t.from == transition.from
+                    && t.event == transition.event
+                    && t.guard == transition.guard
+            }) {
+                return Err(StateMachineAssetError::DuplicateTransition {
+                    from: transition.from.clone(),
+                    event: transition.event.clone(),
+                });
+            }
+        }
+        for state in self.states.iter().filter(|state| !state.terminal) {
+            if !self.transitions.iter().any(|t| t.from == state.name) {
+                return Err(StateMachineAssetError::DeadEnd(state.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every guard and action name the asset refers to
+    pub fn callback_names(&self) -> impl Iterator<Item = &String> {
+        self.states
+            .iter()
+            .flat_map(|state| state.on_enter.iter().chain(state.on_exit.iter()))
+            .chain(
+                self.transitions
+                    .iter()
+                    .flat_map(|t| t.guard.iter().chain(t.actions.iter())),
+            )
+    }
+}
+
+#[derive(Default)]
+pub struct StateMachineAssetLoader;
+
+impl AssetLoader for StateMachineAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let asset: StateMachineAsset = ron::de::from_bytes(bytes)?;
+            asset.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fsm"]
+    }
+}
+
+/// Decides whether a transition may be taken by the entity
+pub type Guard = fn(Entity, &World, &Resources) -> bool;
+
+/// Does some work for the entity as it moves between states
+pub type Action = fn(Entity, &mut World, &mut Resources);
+
+/// The guards and actions a [StateMachineAsset] can refer to by name
+#[derive(Default)]
+pub struct StateMachineCallbacks {
+    guards: HashMap<String, Guard>,
+    actions: HashMap<String, Action>,
+}
+
+impl StateMachineCallbacks {
+    pub fn add_guard(&mut self, name: &str, guard: Guard) -> &mut Self {
+        self.guards.insert(name.to_string(), guard);
+        self
+    }
+
+    pub fn add_action(&mut self, name: &str, action: Action) -> &mut Self {
+        self.actions.insert(name.to_string(), action);
+        self
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.guards.contains_key(name) || self.actions.contains_key(name)
+    }
+}
+
+/// A component running the [StateMachineAsset] behind `handle` for its entity
+pub struct AssetStateMachine {
+    pub handle: Handle<StateMachineAsset>,
+    current: Option<String>,
+}
+
+impl AssetStateMachine {
+    pub fn new(handle: Handle<StateMachineAsset>) -> Self {
+        AssetStateMachine {
+            handle,
+            current: None,
+        }
+    }
+
+    /// The current state, or `None` until the asset has loaded
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+}
+
+/// Sends the named event to the [AssetStateMachine] of `entity`
+#[derive(Debug, Clone)]
+pub struct AssetStateEvent {
+    pub entity: Entity,
+    pub event: String,
+}
+
+#[derive(Default)]
+struct AssetStateMachineReaders {
+    events: EventReader<AssetStateEvent>,
+    asset_events: EventReader<AssetEvent<StateMachineAsset>>,
+}
+
+/// An App Plugin that loads [StateMachineAsset]s and runs every [AssetStateMachine]
+#[derive(Default)]
+pub struct AssetStateMachinePlugin {
+    /// Reloads `.fsm` assets when they change on disk
+    ///
+    /// This turns on the [AssetServer]'s file watching, which reloads every asset in the app
+    /// when it changes, not just state machines.
+    pub hot_reload: bool,
+}
+
+impl Plugin for AssetStateMachinePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<StateMachineAsset>()
+            .init_asset_loader::<StateMachineAssetLoader>()
+            .add_event::<AssetStateEvent>()
+            .init_resource::<StateMachineCallbacks>()
+            .init_resource::<AssetStateMachineReaders>()
+            .add_system(Self::asset_state_machine_system.thread_local_system());
+        if self.hot_reload {
+            app.add_startup_system(Self::watch_for_changes_system.system());
+        }
+    }
+}
+
+impl AssetStateMachinePlugin {
+    fn watch_for_changes_system(asset_server: Res<AssetServer>) {
+        if let Err(error) = asset_server.watch_for_changes() {
+            eprintln!(
+                "Could not watch state machine assets for changes: {:?}",
+                error
+            );
+        }
+    }
+
+    pub fn asset_state_machine_system(world: &mut World, resources: &mut Resources) {
+        let (events, reloaded) = {
+            let mut readers = resources.get_mut::<AssetStateMachineReaders>().unwrap();
+            let events = resources.get::<Events<AssetStateEvent>>().unwrap();
+            let asset_events = resources
+                .get::<Events<AssetEvent<StateMachineAsset>>>()
+                .unwrap();
+            let events = readers.events.iter(&events).cloned().collect::<Vec<_>>();
+            let mut reloaded = Vec::new();
+            for asset_event in readers.asset_events.iter(&asset_events) {
+                match asset_event {
+                    AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                        reloaded.push(handle.clone())
+                    }
+                    AssetEvent::Removed { .. } => {}
+                }
+            }
+            (events, reloaded)
+        };
+
+        for handle in reloaded.iter() {
+            Self::check_callbacks(handle, resources);
+        }
+
+        // start machines whose asset has just loaded, and restart those whose state was removed
+        // by a reload
+        let mut entities = Vec::new();
+        for (entity, machine) in &mut world.query::<(Entity, &AssetStateMachine)>() {
+            entities.push((entity, machine.handle.clone(), machine.current.clone()));
+        }
+        for (entity, handle, current) in entities {
+            let initial = {
+                let assets = resources.get::<Assets<StateMachineAsset>>().unwrap();
+                match assets.get(&handle) {
+                    Some(asset) => match current {
+                        Some(ref current) if asset.state(current).is_some() => None,
+                        _ => Some((
+                            asset.initial.clone(),
+                            Self::enter_actions(asset, &asset.initial),
+                        )),
+                    },
+                    None => None,
+                }
+            };
+            if let Some((initial, actions)) = initial {
+                Self::set_current(world, entity, initial);
+                Self::run_actions(&actions, entity, world, resources);
+            }
+        }
+
+        for AssetStateEvent { entity, event } in events {
+            let (handle, current) = match world.get::<AssetStateMachine>(entity) {
+                Ok(machine) => match machine.current {
+                    Some(ref current) => (machine.handle.clone(), current.clone()),
+                    None => continue,
+                },
+                Err(_) => continue,
+            };
+            let transition = {
+                let assets = resources.get::<Assets<StateMachineAsset>>().unwrap();
+                let callbacks = resources.get::<StateMachineCallbacks>().unwrap();
+                let asset = match assets.get(&handle) {
+                    Some(asset) => asset,
+                    None => continue,
+                };
+                asset
+                    .transitions
+                    .iter()
+                    .filter(|t| t.from == current && t.event == event)
+                    .find(|t| match t.guard {
+                        Some(ref guard) => callbacks
+                            .guards
+                            .get(guard)
+                            .map_or(false, |guard| guard(entity, world, resources)),
+                        None => true,
+                    })
+                    .map(|t| {
+                        let mut actions = Self::exit_actions(asset, &t.from);
+                        actions.extend(t.actions.iter().cloned());
+                        actions.extend(Self::enter_actions(asset, &t.to));
+                        (t.to.clone(), actions)
+                    })
+            };
+            if let Some((to, actions)) = transition {
+                Self::set_current(world, entity, to);
+                Self::run_actions(&actions, entity, world, resources);
+            }
+        }
+    }
+
+    fn enter_actions(asset: &StateMachineAsset, state: &str) -> Vec<String> {
+        asset
+            .state(state)
+            .map_or(Vec::new(), |s| s.on_enter.clone())
+    }
+
+    fn exit_actions(asset: &StateMachineAsset, state: &str) -> Vec<String> {
+        asset.state(state).map_or(Vec::new(), |s| s.on_exit.clone())
+    }
+
+    fn set_current(world: &mut World, entity: Entity, state: String) {
+        if let Ok(mut machine) = world.get_mut::<AssetStateMachine>(entity) {
+            machine.current = Some(state);
+        }
+    }
+
+    fn run_actions(names: &[String], entity: Entity, world: &mut World, resources: &mut Resources) {
+        let actions = {
+            let callbacks = resources.get::<StateMachineCallbacks>().unwrap();
+            names
+                .iter()
+                .filter_map(|name| callbacks.actions.get(name).copied())
+                .collect::<Vec<_>>()
+        };
+        for action in actions {
+            action(entity, world, resources);
+        }
+    }
+
+    /// Guards and actions can't be checked by the loader, so unknown names are reported here
+    fn check_callbacks(handle: &Handle<StateMachineAsset>, resources: &Resources) {
+        let assets = resources.get::<Assets<StateMachineAsset>>().unwrap();
+        let callbacks = resources.get::<StateMachineCallbacks>().unwrap();
+        if let Some(asset) = assets.get(handle) {
+            for name in asset
+                .callback_names()
+                .filter(|name| !callbacks.contains(name))
+            {
+                eprintln!(
+                    "State machine asset refers to unregistered callback {}",
+                    name
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(ron: &str) -> StateMachineAsset {
+        ron::de::from_str(ron).unwrap()
+    }
+
+    fn validate(ron: &str) -> Result<(), StateMachineAssetError> {
+        asset(ron).validate()
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            validate(
+                r#"(
+                    initial: "Idle",
+                    states: [(name: "Idle"), (name: "Dead", terminal: true)],
+                    transitions: [(from: "Idle", event: "Hit", to: "Dead")],
+                )"#
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn unknown_state() {
+        assert_eq!(
+            validate(
+                r#"(
+                    initial: "Idle",
+                    states: [(name: "Idle")],
+                    transitions: [(from: "Idle", event: "Hit", to: "Dead")],
+                )"#
+            ),
+            Err(StateMachineAssetError::UnknownState("Dead".to_string()))
+        );
+        assert_eq!(
+            validate(
+                r#"(initial: "Missing", states: [(name: "Idle", terminal: true)], transitions: [])"#
+            ),
+            Err(StateMachineAssetError::UnknownState("Missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn duplicate_state() {
+        assert_eq!(
+            validate(
+                r#"(
+                    initial: "Idle",
+                    states: [(name: "Idle", terminal: true), (name: "Idle", terminal: true)],
+                    transitions: [],
+                )"#
+            ),
+            Err(StateMachineAssetError::DuplicateState("Idle".to_string()))
+        );
+    }
+
+    #[test]
+    fn duplicate_transition_with_same_guard() {
+        let transitions = |second_guard: &str| {
+            format!(
+                r#"(
+                    initial: "Idle",
+                    states: [(name: "Idle"), (name: "Dead", terminal: true)],
+                    transitions: [
+                        (from: "Idle", event: "Hit", to: "Dead", guard: Some("armed")),
+                        (from: "Idle", event: "Hit", to: "Idle", guard: {}),
+                    ],
+                )"#,
+                second_guard
+            )
+        };
+        assert_eq!(
+            validate(&transitions(r#"Some("armed")"#)),
+            Err(StateMachineAssetError::DuplicateTransition {
+                from: "Idle".to_string(),
+                event: "Hit".to_string(),
+            })
+        );
+        assert_eq!(validate(&transitions(r#"Some("unarmed")"#)), Ok(()));
+        assert_eq!(validate(&transitions("None")), Ok(()));
+    }
+
+    #[test]
+    fn dead_end_unless_terminal() {
+        let states = |terminal: bool| {
+            format!(
+                r#"(
+                    initial: "Idle",
+                    states: [(name: "Idle"), (name: "Dead", terminal: {})],
+                    transitions: [(from: "Idle", event: "Hit", to: "Dead")],
+                )"#,
+                terminal
+            )
+        };
+        assert_eq!(
+            validate(&states(false)),
+            Err(StateMachineAssetError::DeadEnd("Dead".to_string()))
+        );
+        assert_eq!(validate(&states(true)), Ok(()));
+    }
+}
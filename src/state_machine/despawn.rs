@@ -0,0 +1,63 @@
+use super::{MachineState, StateChanged};
+
+use bevy::prelude::*;
+
+/// Marks an entity (and its children) to be despawned when the given state is left
+#[derive(Debug, Clone)]
+pub struct DespawnOnExit<S>(pub S);
+
+pub fn despawn_on_exit_system<S: MachineState>(
+    mut commands: Commands,
+    mut reader: Local<EventReader<StateChanged<S>>>,
+    events: Res<Events<StateChanged<S>>>,
+    mut despawn_query: Query<(Entity, &DespawnOnExit<S>)>,
+) {
+    for changed in reader.iter(&events) {
+        for (entity, despawn_on_exit) in &mut despawn_query.iter() {
+            if changed.exited.contains(&despawn_on_exit.0) {
+                commands.despawn_recursive(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEvent, GameState};
+    use crate::state_machine::StateMachinePlugin;
+    use bevy::ecs::WorldBuilderSource;
+
+    /// A child of a despawned entity
+    struct Child;
+
+    #[test]
+    fn despawns_recursively_on_exit() {
+        let mut builder = App::build();
+        builder.add_plugin(
+            StateMachinePlugin::new(GameState::Starting, GameState::next)
+                .with_hierarchy(GameState::parent, GameState::is_overlay),
+        );
+        let mut app = builder.app;
+        app.world
+            .build()
+            .spawn((DespawnOnExit(GameState::Playing),))
+            .with_children(|parent| {
+                parent.spawn((Child,));
+            })
+            .spawn((DespawnOnExit(GameState::Lose),));
+        let mut events = app.resources.get_mut::<Events<GameEvent>>().unwrap();
+        events.send(GameEvent::StartPause);
+        events.send(GameEvent::Lost);
+        drop(events);
+        app.update();
+        let remaining = app
+            .world
+            .query::<&DespawnOnExit<GameState>>()
+            .iter()
+            .map(|despawn_on_exit| despawn_on_exit.0)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec![GameState::Lose]);
+        assert_eq!(app.world.query::<&Child>().iter().count(), 0);
+    }
+}
@@ -0,0 +1,269 @@
+use super::{MachineEvent, MachineState};
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
+
+/// The states of a state machine and the events connecting them, for inspection and export
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateGraph {
+    pub initial: String,
+    pub states: Vec<String>,
+    /// `(from, event, to)` for every legal transition
+    pub transitions: Vec<(String, String, String)>,
+}
+
+impl StateGraph {
+    pub fn new(initial: &str) -> Self {
+        StateGraph {
+            initial: initial.to_string(),
+            states: vec![initial.to_string()],
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn with_state(mut self, state: &str) -> Self {
+        if !self.states.iter().any(|s| s == state) {
+            self.states.push(state.to_string());
+        }
+        self
+    }
+
+    pub fn with_transition(mut self, from: &str, event: &str, to: &str) -> Self {
+        self = self.with_state(from).with_state(to);
+        self.transitions
+            .push((from.to_string(), event.to_string(), to.to_string()));
+        self
+    }
+
+    /// Builds the graph of a transition function by trying every event against every state
+    ///
+    /// States and events are named by their `Debug` output.
+    pub fn from_transition<S: MachineState, E: MachineEvent>(
+        initial: &S,
+        states: &[S],
+        events: &[E],
+        transition: fn(&S, &E) -> Option<S>,
+    ) -> Self {
+        let mut graph = StateGraph::new(&format!("{:?}", initial));
+        for state in states.iter() {
+            graph = graph.with_state(&format!("{:?}", state));
+            for event in events.iter() {
+                if let Some(next) = transition(state, event) {
+                    graph = graph.with_transition(
+                        &format!("{:?}", state),
+                        &format!("{:?}", event),
+                        &format!("{:?}", next),
+                    );
+                }
+            }
+        }
+        graph
+    }
+
+    /// The states that no sequence of events can reach from the initial state
+    pub fn unreachable_states(&self) -> Vec<&str> {
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        reached.insert(self.initial.as_str());
+        queue.push_back(self.initial.as_str());
+        while let Some(state) = queue.pop_front() {
+            for (_, _, to) in self.transitions.iter().filter(|(from, _, _)| from == state) {
+                if reached.insert(to.as_str()) {
+                    queue.push_back(to.as_str());
+                }
+            }
+        }
+        self.states
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|s| !reached.contains(s))
+            .collect()
+    }
+
+    /// Panics, listing the offending states, if any state is unreachable from the initial state
+    pub fn assert_all_reachable(&self) {
+        let unreachable = self.unreachable_states();
+        if !unreachable.is_empty() {
+            panic!(
+                "states unreachable from {}: {}",
+                self.initial,
+                unreachable.join(", ")
+            );
+        }
+    }
+
+    /// Renders the graph in the Graphviz DOT language
+    pub fn to_dot(&self) -> String {
+        let mut buffer = String::new();
+        buffer += "digraph {\n";
+        writeln!(
+            buffer,
+            "    \"{}\" [shape=doublecircle];",
+            dot_escape(&self.initial)
+        )
+        .unwrap();
+        for state in self.states.iter().filter(|s| **s != self.initial) {
+            writeln!(buffer, "    \"{}\";", dot_escape(state)).unwrap();
+        }
+        for (from, event, to) in self.transitions.iter() {
+            writeln!(
+                buffer,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                dot_escape(from),
+                dot_escape(to),
+                dot_escape(event)
+            )
+            .unwrap();
+        }
+        buffer += "}\n";
+        buffer
+    }
+
+    /// Renders the graph as a Mermaid state diagram
+    pub fn to_mermaid(&self) -> String {
+        let mut buffer = String::new();
+        buffer += "stateDiagram-v2\n";
+        for state in self.states.iter() {
+            writeln!(buffer, "    {}: {}", mermaid_id(state), state).unwrap();
+        }
+        writeln!(buffer, "    [*] --> {}", mermaid_id(&self.initial)).unwrap();
+        for (from, event, to) in self.transitions.iter() {
+            writeln!(
+                buffer,
+                "    {} --> {}: {}",
+                mermaid_id(from),
+                mermaid_id(to),
+                event
+            )
+            .unwrap();
+        }
+        buffer
+    }
+}
+
+/// Escapes the quotes and backslashes in a DOT string, e.g. from a `Debug` name like `Named("a")`
+fn dot_escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Mermaid state ids can't contain spaces or punctuation, so each of those (and `_`) becomes
+/// its hex code point between underscores, keeping e.g. `Level(1)` and `Level[1]` apart
+fn mermaid_id(state: &str) -> String {
+    let mut id = String::new();
+    for c in state.chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c);
+        } else {
+            write!(id, "_{:x}_", c as u32).unwrap();
+        }
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEvent, GameState};
+    use light::Light;
+
+    #[allow(dead_code)]
+    mod light {
+        use crate::typestate_machine;
+
+        typestate_machine! {
+            pub enum Light in lights { Green, Yellow, Red }
+            pub enum LightEvent in light_events { GreenToYellow, YellowToRed, RedToGreen }
+            transitions {
+                Green + GreenToYellow => Yellow,
+                Yellow + YellowToRed => Red,
+                Red + RedToGreen => Green,
+            }
+        }
+    }
+
+    const EVENTS: &[GameEvent] = &[
+        GameEvent::Loaded,
+        GameEvent::StartPause,
+        GameEvent::Restart,
+        GameEvent::Restarted,
+        GameEvent::LevelComplete,
+        GameEvent::Won,
+        GameEvent::Lost,
+    ];
+
+    fn game_graph(states: &[GameState]) -> StateGraph {
+        StateGraph::from_transition(&GameState::Loading, states, EVENTS, GameState::next)
+    }
+
+    #[test]
+    fn typestate_graph() {
+        let graph = Light::graph();
+        assert_eq!(graph.initial, "Green");
+        assert_eq!(graph.states, vec!["Green", "Yellow", "Red"]);
+        assert_eq!(graph.transitions.len(), 3);
+        assert!(graph
+            .transitions
+            .contains(&("Red".into(), "RedToGreen".into(), "Green".into())));
+        graph.assert_all_reachable();
+    }
+
+    #[test]
+    fn game_states_are_reachable() {
+        let graph = game_graph(&[
+            GameState::Loading,
+            GameState::Starting,
+            GameState::Level(1),
+            GameState::Level(2),
+            GameState::Paused,
+            GameState::Playing,
+            GameState::Win,
+            GameState::Lose,
+            GameState::Restarting,
+        ]);
+        assert!(graph.transitions.contains(&(
+            "Level(1)".into(),
+            "LevelComplete".into(),
+            "Level(2)".into()
+        )));
+        graph.assert_all_reachable();
+    }
+
+    #[test]
+    #[should_panic(expected = "states unreachable from Loading: Level(0)")]
+    fn unreachable_game_state_panics() {
+        let graph = game_graph(&[
+            GameState::Loading,
+            GameState::Starting,
+            GameState::Level(0),
+            GameState::Level(1),
+            GameState::Paused,
+            GameState::Playing,
+            GameState::Win,
+            GameState::Lose,
+            GameState::Restarting,
+        ]);
+        assert_eq!(graph.unreachable_states(), vec!["Level(0)"]);
+        graph.assert_all_reachable();
+    }
+
+    #[test]
+    fn dot_output() {
+        let graph = StateGraph::new("Idle").with_transition("Idle", "Go", "Named(\"a\")");
+        assert_eq!(
+            graph.to_dot(),
+            "digraph {\n    \"Idle\" [shape=doublecircle];\n    \"Named(\\\"a\\\")\";\n    \
+             \"Idle\" -> \"Named(\\\"a\\\")\" [label=\"Go\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn mermaid_output() {
+        let graph = StateGraph::new("Level(1)").with_transition("Level(1)", "Next", "Level[1]");
+        assert_eq!(
+            graph.to_mermaid(),
+            "stateDiagram-v2\n    Level_28_1_29_: Level(1)\n    Level_5b_1_5d_: Level[1]\n    \
+             [*] --> Level_28_1_29_\n    Level_28_1_29_ --> Level_5b_1_5d_: Next\n"
+        );
+        assert_ne!(mermaid_id("a_b"), mermaid_id("a b"));
+    }
+}
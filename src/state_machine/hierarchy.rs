@@ -0,0 +1,169 @@
+use super::{MachineState, StateChanged, StateMachine};
+
+/// How the states of a [StateMachine] nest inside each other
+///
+/// A state is active while it or any state nested inside it is current. An overlay state is
+/// entered on top of the current state instead of replacing it: the covered state stays active
+/// underneath, and is restored when the overlay transitions back to it or any of its ancestors.
+///
+/// States are both entered and exited from the outermost to the innermost.
+#[derive(Debug)]
+pub struct Hierarchy<S> {
+    /// The state directly enclosing a state, if any
+    pub parent: fn(&S) -> Option<S>,
+    pub is_overlay: fn(&S) -> bool,
+}
+
+impl<S> Clone for Hierarchy<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Hierarchy<S> {}
+
+impl<S> Default for Hierarchy<S> {
+    fn default() -> Self {
+        Hierarchy {
+            parent: |_| None,
+            is_overlay: |_| false,
+        }
+    }
+}
+
+impl<S: MachineState> StateMachine<S> {
+    /// `state` and the states enclosing it, outermost first
+    pub fn path(&self, state: &S) -> Vec<S> {
+        let mut path = vec![state.clone()];
+        while let Some(parent) = (self.hierarchy.parent)(path.last().unwrap()) {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// Every active state, outermost first, including those covered by overlays
+    pub fn active(&self) -> Vec<S> {
+        let mut active = Vec::new();
+        for state in self.overlaid.iter().chain(std::iter::once(&self.current)) {
+            for state in self.path(state) {
+                if !active.contains(&state) {
+                    active.push(state);
+                }
+            }
+        }
+        active
+    }
+
+    /// Whether `state` is the current state or encloses it
+    pub fn is_in(&self, state: &S) -> bool {
+        self.path(&self.current).contains(state)
+    }
+
+    /// The states covered by overlays, bottom first
+    pub fn overlaid(&self) -> &[S] {
+        &self.overlaid
+    }
+
+    /// Moves to `next`, returning the states exited and entered on the way, outermost first
+    pub(super) fn apply(&mut self, next: S) -> StateChanged<S> {
+        let before = self.active();
+        let from = self.current.clone();
+        let restores = (self.hierarchy.is_overlay)(&from)
+            && self
+                .overlaid
+                .last()
+                .map_or(false, |covered| self.path(covered).contains(&next));
+        if (self.hierarchy.is_overlay)(&next) {
+            self.overlaid
+                .push(std::mem::replace(&mut self.current, next));
+        } else if restores {
+            self.current = self.overlaid.pop().unwrap();
+        } else {
+            self.overlaid.clear();
+            self.current = next;
+        }
+        self.previous = Some(from.clone());
+
+        let after = self.active();
+        StateChanged {
+            from,
+            to: self.current.clone(),
+            exited: before
+                .iter()
+                .filter(|state| !after.contains(state))
+                .cloned()
+                .collect(),
+            entered: after
+                .iter()
+                .filter(|state| !before.contains(state))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameState;
+
+    fn machine(initial: GameState) -> StateMachine<GameState> {
+        StateMachine::new(initial).with_hierarchy(Hierarchy {
+            parent: GameState::parent,
+            is_overlay: GameState::is_overlay,
+        })
+    }
+
+    #[test]
+    fn enters_outermost_first() {
+        let mut machine = machine(GameState::Starting);
+        let changed = machine.apply(GameState::Level(1));
+        assert_eq!(changed.exited, vec![GameState::Starting]);
+        assert_eq!(
+            changed.entered,
+            vec![GameState::Playing, GameState::Level(1)]
+        );
+    }
+
+    #[test]
+    fn overlay_restores_covered_state() {
+        let mut machine = machine(GameState::Level(1));
+        let changed = machine.apply(GameState::Paused);
+        assert!(changed.exited.is_empty());
+        assert_eq!(changed.entered, vec![GameState::Paused]);
+        assert_eq!(machine.overlaid(), &[GameState::Level(1)]);
+        assert!(machine.is_in(&GameState::Playing));
+
+        let changed = machine.apply(GameState::Playing);
+        assert_eq!(changed.to, GameState::Level(1));
+        assert_eq!(changed.exited, vec![GameState::Paused]);
+        assert!(changed.entered.is_empty());
+        assert!(machine.overlaid().is_empty());
+    }
+
+    #[test]
+    fn leaving_overlay_exits_outermost_first() {
+        let mut machine = machine(GameState::Level(1));
+        machine.apply(GameState::Paused);
+        let changed = machine.apply(GameState::Restarting);
+        assert_eq!(
+            changed.exited,
+            vec![GameState::Playing, GameState::Level(1), GameState::Paused]
+        );
+        assert_eq!(changed.entered, vec![GameState::Restarting]);
+        assert!(machine.overlaid().is_empty());
+    }
+
+    #[test]
+    fn sibling_keeps_parent_active() {
+        let mut machine = machine(GameState::Level(1));
+        let changed = machine.apply(GameState::Level(2));
+        assert_eq!(changed.exited, vec![GameState::Level(1)]);
+        assert_eq!(changed.entered, vec![GameState::Level(2)]);
+        assert_eq!(
+            machine.active(),
+            vec![GameState::Playing, GameState::Level(2)]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use super::{Hierarchy, MachineEvent, MachineState, StateMachine, TransitionFn};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// One event handled by a state machine, and the state it led to
+///
+/// `to` is `None` when the event was rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionRecord<S, E> {
+    pub frame: u64,
+    pub from: S,
+    pub event: E,
+    pub to: Option<S>,
+}
+
+/// A bounded history of the events handled by a [StateMachine](super::StateMachine), oldest first
+#[derive(Debug)]
+pub struct StateHistory<S, E> {
+    capacity: usize,
+    frame: u64,
+    records: VecDeque<TransitionRecord<S, E>>,
+}
+
+/// Where a replay first disagreed with its recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDivergence<S, E> {
+    pub index: usize,
+    pub recorded: TransitionRecord<S, E>,
+    pub replayed: Option<S>,
+}
+
+impl<S: MachineState, E: MachineEvent> StateHistory<S, E> {
+    pub fn new(capacity: usize) -> Self {
+        StateHistory {
+            capacity,
+            frame: 0,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub(super) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub(super) fn push(&mut self, from: S, event: E, to: Option<S>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(TransitionRecord {
+            frame: self.frame,
+            from,
+            event,
+            to,
+        });
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &TransitionRecord<S, E>> {
+        self.records.iter()
+    }
+
+    /// The recorded events, in the order they were handled
+    pub fn events(&self) -> Vec<E> {
+        self.records.iter().map(|r| r.event.clone()).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+impl<S: MachineState + Serialize, E: MachineEvent + Serialize> StateHistory<S, E> {
+    pub fn to_ron(&self) -> ron::Result<String> {
+        ron::ser::to_string_pretty(&self.records, ron::ser::PrettyConfig::default())
+    }
+}
+
+pub fn records_from_ron<S: DeserializeOwned, E: DeserializeOwned>(
+    ron: &str,
+) -> ron::Result<Vec<TransitionRecord<S, E>>> {
+    ron::de::from_str(ron)
+}
+
+/// Re-applies recorded events to `transition` from the first recorded state, without an App
+///
+/// Returns the final state, or the first record whose outcome the transition function no longer
+/// reproduces. Overlays covering the first recorded state aren't recorded, so can't be restored.
+pub fn replay<S: MachineState, E: MachineEvent>(
+    records: &[TransitionRecord<S, E>],
+    transition: &TransitionFn<S, E>,
+    hierarchy: Hierarchy<S>,
+) -> Result<Option<S>, ReplayDivergence<S, E>> {
+    let mut machine = match records.first() {
+        Some(record) => StateMachine::new(record.from.clone()).with_hierarchy(hierarchy),
+        None => return Ok(None),
+    };
+    for (index, record) in records.iter().enumerate() {
+        let replayed = transition
+            .next(machine.current(), &record.event)
+            .ok()
+            .map(|next| machine.apply(next).to);
+        if replayed != record.to {
+            return Err(ReplayDivergence {
+                index,
+                recorded: record.clone(),
+                replayed,
+            });
+        }
+    }
+    Ok(Some(machine.current().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEvent, GameState};
+
+    fn hierarchy() -> Hierarchy<GameState> {
+        Hierarchy {
+            parent: GameState::parent,
+            is_overlay: GameState::is_overlay,
+        }
+    }
+
+    /// Handles `events` the way the state machine's transition system does
+    fn record(events: &[GameEvent]) -> StateHistory<GameState, GameEvent> {
+        let transition = TransitionFn::Function(GameState::next);
+        let mut machine = StateMachine::new(GameState::Loading).with_hierarchy(hierarchy());
+        let mut history = StateHistory::new(16);
+        for event in events.iter() {
+            history.next_frame();
+            let from = machine.current().clone();
+            let to = transition
+                .next(&from, event)
+                .ok()
+                .map(|next| machine.apply(next).to);
+            history.push(from, *event, to);
+        }
+        history
+    }
+
+    const EVENTS: &[GameEvent] = &[
+        GameEvent::Loaded,
+        GameEvent::StartPause,
+        GameEvent::LevelComplete,
+        GameEvent::StartPause,
+        GameEvent::StartPause,
+        // rejected, as only Restarting handles it
+        GameEvent::Restarted,
+        GameEvent::Won,
+    ];
+
+    #[test]
+    fn replays_round_tripped_history() {
+        let history = record(EVENTS);
+        let records = records_from_ron::<GameState, GameEvent>(&history.to_ron().unwrap()).unwrap();
+        assert_eq!(records, history.records().cloned().collect::<Vec<_>>());
+        assert_eq!(records[4].to, Some(GameState::Level(2)));
+        assert_eq!(records[5].to, None);
+        assert_eq!(
+            replay(
+                &records,
+                &TransitionFn::Function(GameState::next),
+                hierarchy()
+            ),
+            Ok(Some(GameState::Win))
+        );
+    }
+
+    #[test]
+    fn replay_reports_divergence() {
+        fn skips_levels(state: &GameState, event: &GameEvent) -> Option<GameState> {
+            match (state, event) {
+                (GameState::Level(_), GameEvent::LevelComplete) => Some(GameState::Win),
+                _ => GameState::next(state, event),
+            }
+        }
+        let records = record(EVENTS).records().cloned().collect::<Vec<_>>();
+        let divergence =
+            replay(&records, &TransitionFn::Function(skips_levels), hierarchy()).unwrap_err();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.recorded.to, Some(GameState::Level(2)));
+        assert_eq!(divergence.replayed, Some(GameState::Win));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = StateHistory::new(2);
+        history.push(GameState::Loading, GameEvent::Loaded, None);
+        history.push(GameState::Loading, GameEvent::Restart, None);
+        history.push(GameState::Loading, GameEvent::Won, None);
+        assert_eq!(history.events(), vec![GameEvent::Restart, GameEvent::Won]);
+    }
+}
@@ -0,0 +1,179 @@
+use super::{MachineState, StateChanged, StateMachine};
+
+use bevy::ecs::System;
+use bevy::prelude::*;
+
+/// Systems registered to run on entering, exiting or while in a particular state
+pub struct StateSystems<S> {
+    on_enter: Vec<(S, Box<dyn System>)>,
+    on_exit: Vec<(S, Box<dyn System>)>,
+    on_update: Vec<(S, Box<dyn System>)>,
+    /// The states entered at startup, outermost first
+    initial: Vec<S>,
+    uninitialized: bool,
+    reader: EventReader<StateChanged<S>>,
+}
+
+impl<S> Default for StateSystems<S> {
+    fn default() -> Self {
+        StateSystems {
+            on_enter: Vec::new(),
+            on_exit: Vec::new(),
+            on_update: Vec::new(),
+            initial: Vec::new(),
+            uninitialized: true,
+            reader: Default::default(),
+        }
+    }
+}
+
+impl<S: MachineState> StateSystems<S> {
+    pub(super) fn new(initial: Vec<S>) -> Self {
+        StateSystems {
+            initial,
+            ..Default::default()
+        }
+    }
+
+    fn run_matching(
+        systems: &mut Vec<(S, Box<dyn System>)>,
+        state: &S,
+        world: &mut World,
+        resources: &mut Resources,
+    ) {
+        for (_, system) in systems.iter_mut().filter(|(s, _)| s == state) {
+            system.update_archetype_access(world);
+            system.run(world, resources);
+            system.run_thread_local(world, resources);
+        }
+    }
+
+    /// Runs the exit and enter systems for every transition since the last run, then the update
+    /// systems of the current state and the states enclosing it
+    pub fn run_state_systems(world: &mut World, resources: &mut Resources) {
+        let mut systems = std::mem::take(&mut *resources.get_mut::<StateSystems<S>>().unwrap());
+        let initialize = systems.uninitialized;
+        if initialize {
+            for (_, system) in systems
+                .on_enter
+                .iter_mut()
+                .chain(systems.on_exit.iter_mut())
+                .chain(systems.on_update.iter_mut())
+            {
+                system.initialize(resources);
+            }
+        }
+
+        // the machine may already have moved on this frame, so the initial states are entered
+        // before replaying its transitions
+        let mut transitions = Vec::new();
+        if initialize {
+            transitions.push((Vec::new(), std::mem::take(&mut systems.initial)));
+        }
+        {
+            let events = resources.get::<Events<StateChanged<S>>>().unwrap();
+            for changed in systems.reader.iter(&events) {
+                transitions.push((changed.exited.clone(), changed.entered.clone()));
+            }
+        }
+
+        for (exited, entered) in transitions.iter() {
+            for state in exited.iter() {
+                Self::run_matching(&mut systems.on_exit, state, world, resources);
+            }
+            for state in entered.iter() {
+                Self::run_matching(&mut systems.on_enter, state, world, resources);
+            }
+        }
+
+        let current_path = {
+            let machine = resources.get::<StateMachine<S>>().unwrap();
+            machine.path(machine.current())
+        };
+        for state in current_path.iter() {
+            Self::run_matching(&mut systems.on_update, state, world, resources);
+        }
+
+        systems.uninitialized = false;
+        *resources.get_mut::<StateSystems<S>>().unwrap() = systems;
+    }
+}
+
+/// Adds state-scoped systems to an App
+pub trait StateSystemsAppExt {
+    /// Runs `system` once each time `state` is entered, including when it is the initial state
+    fn on_enter<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self;
+    /// Runs `system` once each time `state` is left
+    fn on_exit<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self;
+    /// Runs `system` every frame while `state` is the current state or encloses it
+    fn on_update<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self;
+}
+
+const MISSING_PLUGIN: &str =
+    "the StateMachinePlugin for this state must be added before its state systems";
+
+impl StateSystemsAppExt for AppBuilder {
+    fn on_enter<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self {
+        self.resources()
+            .get_mut::<StateSystems<S>>()
+            .expect(MISSING_PLUGIN)
+            .on_enter
+            .push((state, system));
+        self
+    }
+
+    fn on_exit<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self {
+        self.resources()
+            .get_mut::<StateSystems<S>>()
+            .expect(MISSING_PLUGIN)
+            .on_exit
+            .push((state, system));
+        self
+    }
+
+    fn on_update<S: MachineState>(&mut self, state: S, system: Box<dyn System>) -> &mut Self {
+        self.resources()
+            .get_mut::<StateSystems<S>>()
+            .expect(MISSING_PLUGIN)
+            .on_update
+            .push((state, system));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEvent, GameState};
+    use crate::state_machine::StateMachinePlugin;
+
+    /// The state systems run so far, in order
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn log(entry: &'static str) -> Box<dyn System> {
+        (move |mut log: ResMut<Log>| log.0.push(entry)).system()
+    }
+
+    #[test]
+    fn event_sent_at_startup_enters_and_exits_once() {
+        let mut builder = App::build();
+        builder
+            .init_resource::<Log>()
+            .add_plugin(StateMachinePlugin::new(GameState::Loading, GameState::next))
+            .on_enter(GameState::Loading, log("enter Loading"))
+            .on_exit(GameState::Loading, log("exit Loading"))
+            .on_enter(GameState::Starting, log("enter Starting"));
+        let mut app = builder.app;
+        app.resources
+            .get_mut::<Events<GameEvent>>()
+            .unwrap()
+            .send(GameEvent::Loaded);
+        app.update();
+        app.update();
+        assert_eq!(
+            app.resources.get::<Log>().unwrap().0,
+            vec!["enter Loading", "exit Loading", "enter Starting"]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use super::{MachineEvent, MachineState};
+
+use std::sync::Arc;
+
+/// Why an event couldn't move a state machine on
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransitionError<S, E> {
+    /// No transition out of `state` handles `event`
+    NoTransition { state: S, event: E },
+    /// Transitions out of `state` handle `event`, but the named guards all failed
+    GuardsFailed {
+        state: S,
+        event: E,
+        guards: Vec<&'static str>,
+    },
+}
+
+impl<S, E> TransitionError<S, E> {
+    pub fn state(&self) -> &S {
+        match self {
+            TransitionError::NoTransition { state, .. } => state,
+            TransitionError::GuardsFailed { state, .. } => state,
+        }
+    }
+
+    pub fn event(&self) -> &E {
+        match self {
+            TransitionError::NoTransition { event, .. } => event,
+            TransitionError::GuardsFailed { event, .. } => event,
+        }
+    }
+}
+
+struct Rule<S, E> {
+    from: fn(&S) -> bool,
+    on: fn(&E) -> bool,
+    guard: Option<(&'static str, fn(&S, &E) -> bool)>,
+    to: fn(&S, &E) -> S,
+}
+
+/// A list of transitions, tried in order, that can be guarded on and built from the data in the
+/// current state and the event
+///
+/// ```ignore
+/// let table = TransitionTable::new()
+///     .on_if(
+///         |s| matches!(s, State::Waiting { .. }),
+///         |e| matches!(e, Event::InsertBottle { .. }),
+///         "bottle fits",
+///         |_, e| matches!(e, Event::InsertBottle { size } if (1..=2).contains(size)),
+///         |_, e| match e {
+///             Event::InsertBottle { size } => State::Filling { rate: 10 / size },
+///             _ => unreachable!(),
+///         },
+///     );
+/// ```
+pub struct TransitionTable<S, E> {
+    rules: Vec<Rule<S, E>>,
+}
+
+impl<S: MachineState, E: MachineEvent> Default for TransitionTable<S, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: MachineState, E: MachineEvent> TransitionTable<S, E> {
+    pub fn new() -> Self {
+        TransitionTable { rules: Vec::new() }
+    }
+
+    /// Moves from states matching `from` on events matching `on` to the state built by `to`
+    pub fn on(mut self, from: fn(&S) -> bool, on: fn(&E) -> bool, to: fn(&S, &E) -> S) -> Self {
+        self.rules.push(Rule {
+            from,
+            on,
+            guard: None,
+            to,
+        });
+        self
+    }
+
+    /// Like [on](TransitionTable::on), but only if the guard called `name` passes
+    pub fn on_if(
+        mut self,
+        from: fn(&S) -> bool,
+        on: fn(&E) -> bool,
+        name: &'static str,
+        guard: fn(&S, &E) -> bool,
+        to: fn(&S, &E) -> S,
+    ) -> Self {
+        self.rules.push(Rule {
+            from,
+            on,
+            guard: Some((name, guard)),
+            to,
+        });
+        self
+    }
+
+    pub fn next(&self, state: &S, event: &E) -> Result<S, TransitionError<S, E>> {
+        let mut failed_guards = Vec::new();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| (rule.from)(state) && (rule.on)(event))
+        {
+            match rule.guard {
+                Some((name, guard)) if !guard(state, event) => failed_guards.push(name),
+                _ => return Ok((rule.to)(state, event)),
+            }
+        }
+        if failed_guards.is_empty() {
+            Err(TransitionError::NoTransition {
+                state: state.clone(),
+                event: event.clone(),
+            })
+        } else {
+            Err(TransitionError::GuardsFailed {
+                state: state.clone(),
+                event: event.clone(),
+                guards: failed_guards,
+            })
+        }
+    }
+}
+
+/// How a [StateMachinePlugin](super::StateMachinePlugin) picks the next state
+pub enum TransitionFn<S, E> {
+    Function(fn(&S, &E) -> Option<S>),
+    Table(Arc<TransitionTable<S, E>>),
+}
+
+impl<S, E> Clone for TransitionFn<S, E> {
+    fn clone(&self) -> Self {
+        match self {
+            TransitionFn::Function(function) => TransitionFn::Function(*function),
+            TransitionFn::Table(table) => TransitionFn::Table(table.clone()),
+        }
+    }
+}
+
+impl<S: MachineState, E: MachineEvent> TransitionFn<S, E> {
+    pub fn next(&self, state: &S, event: &E) -> Result<S, TransitionError<S, E>> {
+        match self {
+            TransitionFn::Function(function) => {
+                function(state, event).ok_or_else(|| TransitionError::NoTransition {
+                    state: state.clone(),
+                    event: event.clone(),
+                })
+            }
+            TransitionFn::Table(table) => table.next(state, event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum State {
+        Waiting,
+        Filling { rate: usize },
+        Rejected,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Event {
+        InsertBottle { size: usize },
+        Full,
+    }
+
+    fn waiting(state: &State) -> bool {
+        *state == State::Waiting
+    }
+
+    fn insert_bottle(event: &Event) -> bool {
+        matches!(event, Event::InsertBottle { .. })
+    }
+
+    fn bottle_fits(_: &State, event: &Event) -> bool {
+        matches!(event, Event::InsertBottle { size } if (1..=2).contains(size))
+    }
+
+    fn fill(_: &State, event: &Event) -> State {
+        match event {
+            Event::InsertBottle { size } => State::Filling { rate: 10 / size },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rules_are_tried_in_order() {
+        let table = TransitionTable::new()
+            .on_if(waiting, insert_bottle, "bottle fits", bottle_fits, fill)
+            .on(waiting, insert_bottle, |_, _| State::Rejected)
+            .on(waiting, insert_bottle, |_, _| State::Waiting);
+        assert_eq!(
+            table.next(&State::Waiting, &Event::InsertBottle { size: 1 }),
+            Ok(State::Filling { rate: 10 })
+        );
+        // a failed guard falls through to the next rule
+        assert_eq!(
+            table.next(&State::Waiting, &Event::InsertBottle { size: 3 }),
+            Ok(State::Rejected)
+        );
+    }
+
+    #[test]
+    fn failed_guards_are_named() {
+        let table = TransitionTable::new()
+            .on_if(waiting, insert_bottle, "bottle fits", bottle_fits, fill)
+            .on_if(waiting, insert_bottle, "never", |_, _| false, fill);
+        assert_eq!(
+            table.next(&State::Waiting, &Event::InsertBottle { size: 0 }),
+            Err(TransitionError::GuardsFailed {
+                state: State::Waiting,
+                event: Event::InsertBottle { size: 0 },
+                guards: vec!["bottle fits", "never"],
+            })
+        );
+        assert_eq!(
+            table.next(&State::Waiting, &Event::Full),
+            Err(TransitionError::NoTransition {
+                state: State::Waiting,
+                event: Event::Full,
+            })
+        );
+    }
+
+    #[test]
+    fn payload_reaches_next_state() {
+        let table = TransitionTable::new()
+            .on_if(waiting, insert_bottle, "bottle fits", bottle_fits, fill)
+            .on(
+                |state| matches!(state, State::Filling { .. }),
+                |event| *event == Event::Full,
+                |_, _| State::Waiting,
+            );
+        let filling = table
+            .next(&State::Waiting, &Event::InsertBottle { size: 2 })
+            .unwrap();
+        assert_eq!(filling, State::Filling { rate: 5 });
+        assert_eq!(table.next(&filling, &Event::Full), Ok(State::Waiting));
+    }
+}
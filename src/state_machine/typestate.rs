@@ -0,0 +1,125 @@
+/// A transition out of the state `Self` on the event `E`, checked at compile time
+///
+/// Calling `next` with an event that has no transition out of the current state is a compile
+/// error, as in `sandbox/fsm-compiletime3.rs`.
+pub trait Transition<E, Next> {
+    fn next(self, event: E) -> Next;
+}
+
+/// Declares a state machine once, generating both its typestate and enumerable forms
+///
+/// Each state and event becomes a unit struct in its own module, with a [Transition] impl per
+/// transition. Each is also wrapped in an enum, and the states enum gets a
+/// `fn next(&self, event: &Events) -> Option<States>` that can drive a
+/// [StateMachinePlugin](super::StateMachinePlugin). The first state is the `Default`, and
+/// `fn graph() -> StateGraph` describes the whole declaration.
+///
+/// ```ignore
+/// typestate_machine! {
+///     pub enum Light in lights { Green, Yellow, Red }
+///     pub enum LightEvent in light_events { GreenToYellow, YellowToGreen, YellowToRed, RedToGreen }
+///     transitions {
+///         Green + GreenToYellow => Yellow,
+///         Yellow + YellowToGreen => Green,
+///         Yellow + YellowToRed => Red,
+///         Red + RedToGreen => Green,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! typestate_machine {
+    (
+        $vis:vis enum $states:ident in $states_mod:ident { $first:ident $(, $state:ident)* $(,)? }
+        $evis:vis enum $events:ident in $events_mod:ident { $($event:ident),+ $(,)? }
+        transitions { $($from:ident + $via:ident => $to:ident),+ $(,)? }
+    ) => {
+        $vis mod $states_mod {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+            pub struct $first;
+            $(
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+                pub struct $state;
+            )*
+        }
+
+        $evis mod $events_mod {
+            $(
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+                pub struct $event;
+            )+
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis enum $states {
+            $first($states_mod::$first),
+            $($state($states_mod::$state),)*
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $evis enum $events {
+            $($event($events_mod::$event),)+
+        }
+
+        impl Default for $states {
+            fn default() -> Self {
+                $states::$first($states_mod::$first)
+            }
+        }
+
+        impl From<$states_mod::$first> for $states {
+            fn from(state: $states_mod::$first) -> Self {
+                $states::$first(state)
+            }
+        }
+
+        $(
+            impl From<$states_mod::$state> for $states {
+                fn from(state: $states_mod::$state) -> Self {
+                    $states::$state(state)
+                }
+            }
+        )*
+
+        $(
+            impl From<$events_mod::$event> for $events {
+                fn from(event: $events_mod::$event) -> Self {
+                    $events::$event(event)
+                }
+            }
+        )+
+
+        $(
+            impl $crate::state_machine::Transition<$events_mod::$via, $states_mod::$to>
+                for $states_mod::$from
+            {
+                fn next(self, _event: $events_mod::$via) -> $states_mod::$to {
+                    $states_mod::$to
+                }
+            }
+        )+
+
+        impl $states {
+            /// The state reached from `self` on `event`, if there is a transition for it
+            #[allow(unreachable_patterns)]
+            pub fn next(&self, event: &$events) -> Option<$states> {
+                match (self, event) {
+                    $(
+                        ($states::$from(state), $events::$via(event)) => Some($states::$to(
+                            $crate::state_machine::Transition::<_, $states_mod::$to>::next(
+                                *state, *event,
+                            ),
+                        )),
+                    )+
+                    _ => None,
+                }
+            }
+
+            /// The declared states and transitions, for export or reachability checks
+            pub fn graph() -> $crate::state_machine::StateGraph {
+                $crate::state_machine::StateGraph::new(stringify!($first))
+                    $(.with_state(stringify!($state)))*
+                    $(.with_transition(stringify!($from), stringify!($via), stringify!($to)))+
+            }
+        }
+    };
+}